@@ -0,0 +1,140 @@
+// Compares the plookup and LogUp lookup chips: advice columns used, in the name of each
+// benchmark group, and proving time with the IPA backend, for the same lookup table and value
+// table filling circuits of growing size
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use halo2_proofs::{
+    circuit::{floor_planner::V1, Layouter, Value},
+    plonk::*,
+};
+use halo2curves::pasta::{EqAffine, Fp};
+use lookup::{Halo2Ipa, LogUpChip, LogUpConfig, LookupChip, LookupConfig};
+use rand_core::{OsRng, RngCore};
+use vrt::backend::ProofSystem;
+
+const WIDTH: usize = 2;
+
+#[derive(Clone, Debug)]
+enum BenchConfig {
+    Plookup(LookupConfig),
+    LogUp(LogUpConfig),
+}
+
+// Tables of `height` rows given column by column; LOGUP selects the argument
+#[derive(Clone)]
+struct BenchCircuit<const LOGUP: bool> {
+    height: usize,
+    lookup_table: Value<Vec<Vec<Fp>>>,
+    value_table: Value<Vec<Vec<Fp>>>,
+}
+
+impl<const LOGUP: bool> BenchCircuit<LOGUP> {
+    // a table of distinct rows and values picked at random from it
+    fn rand(height: usize) -> Self {
+        let lookup_table: Vec<Vec<Fp>> = (0..WIDTH)
+            .map(|j| {
+                (0..height)
+                    .map(|i| Fp::from((i * WIDTH + j) as u64))
+                    .collect()
+            })
+            .collect();
+        let picks: Vec<usize> = (0..height)
+            .map(|_| OsRng.next_u32() as usize % height)
+            .collect();
+        let value_table = lookup_table
+            .iter()
+            .map(|column| picks.iter().map(|&pick| column[pick]).collect())
+            .collect();
+        Self {
+            height,
+            lookup_table: Value::known(lookup_table),
+            value_table: Value::known(value_table),
+        }
+    }
+}
+
+impl<const LOGUP: bool> Circuit<Fp> for BenchCircuit<LOGUP> {
+    type Config = BenchConfig;
+    type FloorPlanner = V1;
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            height: self.height,
+            lookup_table: Value::unknown(),
+            value_table: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let lookup_table = (0..WIDTH)
+            .map(|_| meta.advice_column_in(FirstPhase).into())
+            .collect();
+        let value_table = (0..WIDTH)
+            .map(|_| meta.advice_column_in(FirstPhase))
+            .collect();
+        if LOGUP {
+            BenchConfig::LogUp(LogUpChip::configure(meta, lookup_table, value_table))
+        } else {
+            BenchConfig::Plookup(LookupChip::configure(meta, lookup_table, value_table))
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Fp>) -> Result<(), Error> {
+        let (lookup_table, value_table) = (self.lookup_table.clone(), self.value_table.clone());
+        match config {
+            BenchConfig::Plookup(config) => LookupChip::construct(config).assign(
+                layouter,
+                self.height,
+                self.height,
+                lookup_table,
+                value_table,
+            ),
+            BenchConfig::LogUp(config) => LogUpChip::construct(config).assign(
+                layouter,
+                self.height,
+                self.height,
+                lookup_table,
+                value_table,
+            ),
+        }
+    }
+}
+
+fn constraint_system<const LOGUP: bool>() -> ConstraintSystem<Fp> {
+    let mut meta = ConstraintSystem::<Fp>::default();
+    BenchCircuit::<LOGUP>::configure(&mut meta);
+    meta
+}
+
+// Both arguments prove tables of the same height: the tallest that fit in 2^k rows with either
+// chip, as their blinding rows may differ
+fn height(k: u32) -> usize {
+    LookupChip::max_height(&constraint_system::<false>(), k)
+        .min(LogUpChip::max_height(&constraint_system::<true>(), k))
+}
+
+fn bench_argument<const LOGUP: bool>(c: &mut Criterion, name: &str) {
+    let columns = constraint_system::<LOGUP>().num_advice_columns();
+    let mut group = c.benchmark_group(format!("prove {} ({} advice columns)", name, columns));
+    group.sample_size(10);
+    for k in [8, 10, 12] {
+        let circuit = BenchCircuit::<LOGUP>::rand(height(k));
+        let system = Halo2Ipa::<EqAffine, BenchCircuit<LOGUP>>::new(k);
+        let (pk, vk) = system.setup(&circuit).unwrap();
+        let proof = system.prove(&pk, circuit.clone(), &vec![]).unwrap();
+        assert!(system.verify(&vk, &vec![], &proof).unwrap());
+
+        group.bench_with_input(BenchmarkId::from_parameter(k), &k, |b, _| {
+            b.iter(|| system.prove(&pk, circuit.clone(), &vec![]).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn lookup_benchmark(c: &mut Criterion) {
+    bench_argument::<false>(c, "plookup");
+    bench_argument::<true>(c, "logup");
+}
+
+criterion_group!(benches, lookup_benchmark);
+criterion_main!(benches);
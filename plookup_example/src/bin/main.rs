@@ -1,30 +1,19 @@
-use ff::{BatchInvert, Field};
 use halo2_proofs::{
     arithmetic::{CurveAffine, FieldExt},
-    circuit::{floor_planner::V1, Layouter, Value, Region},
+    circuit::{floor_planner::V1, Layouter, Value},
     dev::{metadata, FailureLocation, MockProver, VerifyFailure},
     halo2curves::pasta::EqAffine,
     plonk::*,
-    poly::{
-        commitment::ParamsProver,
-        ipa::{
-            commitment::{IPACommitmentScheme, ParamsIPA},
-            multiopen::{ProverIPA, VerifierIPA},
-            strategy::AccumulatorStrategy,
-        },
-        Rotation, VerificationStrategy,
-    },
-    transcript::{
-        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
-    },
 };
 use halo2curves::pasta::Fp;
+use lookup::{arrange, pad, Halo2Ipa, LogUpChip, LogUpConfig, LookupChip, LookupConfig};
 use rand_core::{OsRng, RngCore};
-use std::{iter, cmp::Ordering};
+use std::{env, fs, process};
+use vrt::backend::{self, Backend};
 
 // sample a random table represented by a vector of vector. 
-// The vector has HEIGHT rows where each row is a vector
-// Each row has WIDTH elements
+// The vector has height rows where each row is a vector
+// Each row has width elements
 fn rand_2d_vector<Field: FieldExt, Rng: RngCore> (
     rng: &mut Rng,
     width: usize,
@@ -59,135 +48,32 @@ fn sample_random_values_from_lookup_table<Field: FieldExt, Rng: RngCore> (
     res
 }
 
-// receive a table of 
-fn pad_array<Field: FieldExt> (
-    original: &Vec<Vec<Field>>,
-    width: usize,
-    new_height: usize,
-) -> Vec<Vec<Field>> {
-    assert!(new_height >= original.len(), "NEW_HEIGHT ({}) must be at least CURRENT_HEIGHT ({})", new_height, original.len());
-    for row in original.iter() {
-        assert_eq!(row.len(), width);
-    }
-    let mut res: Vec<Vec<Field>> = vec![vec![Field::zero(); width]; new_height]; 
-    for i in 0..original.len() {
-        res[i] = original[i].clone();
-    }
-
-    for i in original.len()..new_height {
-        res[i] = res[0].clone();
-    }
-
-    res
-}
-
-#[derive(Clone)]
-struct MyConfig<const WIDTH: usize> {
-    q_lookup: Selector,
-    q_first_lookup: Selector,
-    q_last_lookup: Selector,
-    q_first_row_equal: Selector,
-    q_other_row_equal: Selector,
-    lookup_table: [Column<Advice>; WIDTH],
-    value_table: [Column<Advice>; WIDTH],
-    arranged_lookup_table: [Column<Advice>; WIDTH],
-    arranged_value_table: [Column<Advice>; WIDTH],
-    theta: Challenge,
-    beta: Challenge,
-    gamma: Challenge,
-    z_lookup: Column<Advice>,
+#[derive(Clone, Debug)]
+enum MyConfig {
+    Plookup(LookupConfig),
+    LogUp(LogUpConfig),
 }
 
-impl<const Width: usize> MyConfig<Width> {
-    fn configure<Field: FieldExt>(meta: &mut ConstraintSystem<Field>) -> Self {
-        let [q_lookup, q_first_lookup, q_last_lookup, q_first_row_equal, q_other_row_equal] = [(); 5].map(|_| meta.selector());
-        let lookup_table = [(); Width].map(|_| meta.advice_column_in(FirstPhase));
-        let value_table = [(); Width].map(|_| meta.advice_column_in(FirstPhase));
-        let arranged_lookup_table = [(); Width].map(|_| meta.advice_column_in(FirstPhase));
-        let arranged_value_table = [(); Width].map(|_| meta.advice_column_in(FirstPhase));
-        let [theta, beta, gamma] = [(); 3].map(|_| meta.challenge_usable_after(FirstPhase));
-        let z_lookup = meta.advice_column_in(SecondPhase);
-
-        meta.create_gate("z should start with 1", |meta| {
-            let q_first_lookup = meta.query_selector(q_first_lookup);
-            let z_lookup = meta.query_advice(z_lookup, Rotation::cur());
-            let one = Expression::Constant(Field::one());
-            vec![q_first_lookup * (one - z_lookup)]
-        });
-
-        meta.create_gate("z should end with 1", |meta| {
-            let q_last_lookup = meta.query_selector(q_last_lookup);
-            let z_lookup = meta.query_advice(z_lookup, Rotation::cur());
-            let one = Expression::Constant(Field::one());
-
-            vec![q_last_lookup * (one - z_lookup)]
-        });
-
-        meta.create_gate("z should have valid transition", |meta| {
-            let q_lookup = meta.query_selector(q_lookup);
-            let lookup_table = lookup_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let value_table = value_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let arranged_lookup_table = arranged_lookup_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let arranged_value_table = arranged_value_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let [theta, beta, gamma] = [theta, beta, gamma].map(|challenge| meta.query_challenge(challenge));
-            let [z, z_w] = [Rotation::cur(), Rotation::next()].map(|rotation| meta.query_advice(z_lookup, rotation));
-
-            let lookup_merged = lookup_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            let value_merged = value_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            let arranged_lookup_merged = arranged_lookup_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            let arranged_value_merged = arranged_value_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            vec![q_lookup * (z_w * (arranged_lookup_merged + gamma.clone()) * (arranged_value_merged + beta.clone()) - z * (lookup_merged + gamma) * (value_merged + beta))]
-        });
-
-        meta.create_gate("first rows of arranged_lookup_table and arranged_value_table are equal", |meta| {
-            let q_first_row_equal = meta.query_selector(q_first_row_equal);
-            let arranged_lookup_table = arranged_lookup_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let arranged_value_table = arranged_value_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let theta = meta.query_challenge(theta);
-
-            let arranged_lookup_merged = arranged_lookup_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            let arranged_value_merged = arranged_value_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            vec![q_first_row_equal * (arranged_lookup_merged - arranged_value_merged)]
-        });
-
-        meta.create_gate("each of other rows of arranged_value_table is equal to previous row or equal to the same one in arranged_lookup_table", |meta| {
-            let q_other_row_equal = meta.query_selector(q_other_row_equal);
-            let arranged_lookup_table = arranged_lookup_table.map(|advice| meta.query_advice(advice, Rotation::cur()));
-            let [arranged_value_table, prev_arranged_value_table] = [Rotation::cur(), Rotation::prev()].map(
-                |rotation| arranged_value_table.map(|advice| meta.query_advice(advice, rotation))
-            );
-            let theta = meta.query_challenge(theta);
-
-            let arranged_lookup_merged = arranged_lookup_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            let arranged_value_merged = arranged_value_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            let prev_arranged_value_merged = prev_arranged_value_table.iter().cloned().reduce(|acc, v| acc * theta.clone() + v).unwrap();
-            vec![q_other_row_equal * (arranged_lookup_merged - arranged_value_merged.clone()) * (arranged_value_merged - prev_arranged_value_merged)]
-        });
-
-        Self {
-            q_lookup: q_lookup,
-            q_first_lookup: q_first_lookup,
-            q_last_lookup: q_last_lookup,
-            q_first_row_equal: q_first_row_equal,
-            q_other_row_equal: q_other_row_equal,
-            lookup_table: lookup_table,
-            value_table: value_table,
-            arranged_lookup_table: arranged_lookup_table,
-            arranged_value_table: arranged_value_table,
-            theta: theta,
-            beta: beta,
-            gamma: gamma,
-            z_lookup: z_lookup,
-        }
-    }
+// Shape of the circuit, which halo2 passes to `Circuit::configure_with_params`: `width` is the
+// number of columns of both tables, `fixed` puts the lookup table in fixed columns, and `logup`
+// uses the LogUp argument instead of plookup
+#[derive(Clone, Copy, Debug, Default)]
+struct MyParams {
+    width: usize,
+    fixed: bool,
+    logup: bool,
 }
 
-#[derive(Default, Clone)]
-struct MyCircuit<Field: FieldExt, const WIDTH: usize, const HEIGHT: usize> {
-    lookup_table: Value<[[Field; HEIGHT]; WIDTH]>,
-    value_table: Value<[[Field; HEIGHT]; WIDTH]>,
-    arranged_lookup_table: Value<[[Field; HEIGHT]; WIDTH]>,
-    arranged_value_table: Value<[[Field; HEIGHT]; WIDTH]>,
+// Tables are stored column by column, the lookup table with `table_height` rows and the value
+// table with `query_height` rows. A fixed lookup table is part of the verifying key, so it is
+// kept when the circuit is synthesized without witnesses.
+#[derive(Clone)]
+struct MyCircuit<Field: FieldExt> {
+    params: MyParams,
+    table_height: usize,
+    query_height: usize,
+    lookup_table: Value<Vec<Vec<Field>>>,
+    value_table: Value<Vec<Vec<Field>>>,
 }
 
 fn print_vec_table<Field: FieldExt>(table: &Vec<Vec<Field>>) {
@@ -199,264 +85,198 @@ fn print_vec_table<Field: FieldExt>(table: &Vec<Vec<Field>>) {
     }
 }
 
-fn print_array_table<Field: FieldExt, const WIDTH: usize, const HEIGHT: usize>(table: &Value<[[Field; HEIGHT]; WIDTH]>) {
-    table.map(|table_value| {
-        for i in 0..HEIGHT {
-            for j in 0..WIDTH {
-                print!("{} ", table_value[j][i].get_lower_32());
+fn print_column_table<Field: FieldExt>(table: &Value<Vec<Vec<Field>>>, height: usize) {
+    table.as_ref().map(|table_value| {
+        for i in 0..height {
+            for column in table_value.iter() {
+                print!("{} ", column[i].get_lower_32());
             }
             println!();
         }
     });
 }
 
+// read a table from a file with one row per line, the elements of a row being separated by commas
+fn read_table<Field: FieldExt>(path: &str) -> Result<Vec<Vec<Field>>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split(',')
+                .map(|element| {
+                    element.trim().parse::<u64>()
+                        .map(Field::from)
+                        .map_err(|e| format!("invalid element {:?} in {}: {}", element, path, e))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// read the lookup table and the value table from two files, checking that their rows have the
+// same width and that every value is in the lookup table
+fn read_tables<Field: FieldExt>(lookup_table_path: &str, value_table_path: &str) -> Result<(Vec<Vec<Field>>, Vec<Vec<Field>>), String> {
+    let lookup_table = read_table::<Field>(lookup_table_path)?;
+    let value_table = read_table::<Field>(value_table_path)?;
+    if lookup_table.is_empty() {
+        return Err(format!("{} holds no rows", lookup_table_path));
+    }
+    let width = lookup_table[0].len();
+    if let Some(row) = lookup_table.iter().chain(value_table.iter()).find(|row| row.len() != width) {
+        return Err(format!("all rows must have {} elements, found a row of {}", width, row.len()));
+    }
+    // report a missing value before the circuit is synthesized
+    let (padded_lookup_table, padded_value_table) = pad(lookup_table.clone(), value_table.clone());
+    arrange(&padded_lookup_table, &padded_value_table).map_err(|e| format!("{}: {}", value_table_path, e))?;
+    Ok((lookup_table, value_table))
+}
+
+impl<Field: FieldExt> MyCircuit<Field> {
+    // build a circuit from tables given row by row, of any heights, whose rows have `params.width` elements
+    fn new(params: MyParams, lookup_table: Vec<Vec<Field>>, value_table: Vec<Vec<Field>>) -> Self {
+        assert!(!lookup_table.is_empty(), "lookup table must not be empty");
+        for row in lookup_table.iter().chain(value_table.iter()) {
+            assert_eq!(row.len(), params.width, "rows of the tables must have {} elements", params.width);
+        }
+
+        // store the tables column by column
+        let transform_to_columns = |table: &Vec<Vec<Field>>| -> Vec<Vec<Field>> {
+            (0..params.width).map(|j| table.iter().map(|row| row[j]).collect()).collect()
+        };
+
+        Self {
+            params,
+            table_height: lookup_table.len(),
+            query_height: value_table.len(),
+            lookup_table: Value::known(transform_to_columns(&lookup_table)),
+            value_table: Value::known(transform_to_columns(&value_table)),
+        }
+    }
 
-impl<Field: FieldExt, const WIDTH: usize, const HEIGHT: usize> MyCircuit<Field, WIDTH, HEIGHT> {
     fn rand<Rng: RngCore>(
         rng: &mut Rng,
+        params: MyParams,
         lookup_table_height: usize,
         value_table_height: usize,
     ) -> Self {
-        assert!(lookup_table_height <= HEIGHT, "lookup_table_height must be at most HEIGHT");
-        assert!(value_table_height <= HEIGHT, "value_table_height must be at most HEIGHT");
-        
         // generate random lookup and value tables where rows of value table are sampled from rows of lookup table
-        let original_lookup_table = rand_2d_vector::<Field, Rng>(rng, WIDTH, lookup_table_height);
-        let original_value_table = sample_random_values_from_lookup_table::<Field, Rng>(&original_lookup_table, rng, WIDTH, value_table_height);
-
-        let lookup_table = pad_array::<Field>(&original_lookup_table, WIDTH, HEIGHT);
-        let value_table = pad_array::<Field>(&original_value_table, WIDTH, HEIGHT);
-
-        // defining the comparison function
-        let cmp_rows = |first: &Vec<Field>, second: &Vec<Field>| -> Ordering {
-            assert_eq!(first.len(), WIDTH);
-            assert_eq!(second.len(), WIDTH);
-            let mut res = Ordering::Equal;
-            for i in 0..first.len() {
-                let ordering = first[i].get_lower_128().cmp(&second[i].get_lower_128()); 
-                if ordering.ne(&Ordering::Equal) {
-                    res = ordering;
-                    break;
-                }
-            }
+        let lookup_table = rand_2d_vector::<Field, Rng>(rng, params.width, lookup_table_height);
+        let value_table = sample_random_values_from_lookup_table::<Field, Rng>(&lookup_table, rng, params.width, value_table_height);
 
-            res
-        };
+        Self::new(params, lookup_table, value_table)
+    }
 
-        // sort the tables
-        let [mut sorted_lookup_table, mut sorted_value_table] = [lookup_table.clone(), value_table.clone()];
-        sorted_lookup_table.sort_by(cmp_rows);
-        sorted_value_table.sort_by(cmp_rows);
-
-        // print_vec_table::<Field>(&sorted_lookup_table);
-        // println!("----------");
-        // print_vec_table::<Field>(&sorted_value_table);
-
-        // check validity of 2 sorted tables and re-arrange sorted_lookup_table
-        // I use 2-pointer trick here
-        let mut arranged_lookup_table = vec![vec![Field::zero(); WIDTH]; HEIGHT];
-        let mut selected_rows = [false; HEIGHT];
-        let mut filled_rows = [false; HEIGHT];
-
-        let mut checking_index = 0;
-        let mut is_row_changed = true;
-        
-        for (index, value_row) in sorted_value_table.iter().enumerate() {
-            while cmp_rows(&sorted_lookup_table[checking_index], value_row) == Ordering::Less {
-                checking_index += 1;
-                is_row_changed = true;
-            }
-            // println!("index: {}, checking index: {}", index, checking_index);
-            // assert_eq!(cmp_rows(&sorted_value_table[checking_index], value_row), Ordering::Equal);
-            if is_row_changed {
-                selected_rows[checking_index] = true;
-                filled_rows[index] = true;
-                arranged_lookup_table[index] = value_row.clone();
-                is_row_changed = false;
-            }
-        }
+    fn height(&self) -> usize {
+        self.table_height.max(self.query_height)
+    }
 
-        checking_index = 0;
-        for index in 0..sorted_value_table.len() {
-            if !filled_rows[index] {
-                while selected_rows[checking_index] {
-                    checking_index += 1;
-                }
-                arranged_lookup_table[index] = sorted_lookup_table[checking_index].clone();
-                checking_index += 1;
-            }
-        }
+    // blinding rows halo2 reserves at the end of each column of a circuit of the given shape
+    fn blinding_factors(params: MyParams) -> usize {
+        let mut meta = ConstraintSystem::<Field>::default();
+        Self::configure_with_params(&mut meta, params);
+        meta.blinding_factors()
+    }
 
-        // now arrange the result
-        let transform_to_array = |table: &Vec<Vec<Field>>| -> [[Field; HEIGHT]; WIDTH] {
-            let mut res = [[Field::zero(); HEIGHT]; WIDTH];
-            for i in 0..HEIGHT {
-                for j in 0..WIDTH {
-                    res[j][i] = table[i][j];
-                }
-            }
-            res
-        };
+    // tallest tables of the given shape that fit in 2^k rows
+    fn max_height(params: MyParams, k: u32) -> usize {
+        let mut meta = ConstraintSystem::<Field>::default();
+        Self::configure_with_params(&mut meta, params);
+        LookupChip::max_height(&meta, k)
+    }
 
-        Self {
-            lookup_table: Value::known(transform_to_array(&lookup_table)),
-            value_table: Value::known(transform_to_array(&value_table)),
-            arranged_lookup_table: Value::known(transform_to_array(&arranged_lookup_table)),
-            arranged_value_table: Value::known(transform_to_array(&sorted_value_table)),
+    // check that the tables fit in 2^k rows, besides the last row of z and the blinding rows
+    fn check_k(&self, k: u32) -> Result<(), String> {
+        let max_height = Self::max_height(self.params, k);
+        if self.height() > max_height {
+            return Err(format!(
+                "tables of {} rows do not fit in 2^{} rows: the lookup takes {} rows and halo2 reserves {} blinding rows and one more, so at most {} rows fit",
+                self.height(),
+                k,
+                self.height() + 1,
+                Self::blinding_factors(self.params),
+                max_height,
+            ));
         }
+        Ok(())
+    }
+
+    // smallest k whose 2^k rows hold the tables
+    fn k(&self) -> u32 {
+        let rows = self.height() + Self::blinding_factors(self.params) + 2;
+        rows.next_power_of_two().trailing_zeros()
     }
 }
 
-impl<Field: FieldExt, const WIDTH: usize, const HEIGHT: usize> Circuit<Field> for MyCircuit<Field, WIDTH, HEIGHT> {
-    type Config = MyConfig<WIDTH>;
+impl<Field: FieldExt> Circuit<Field> for MyCircuit<Field> {
+    type Config = MyConfig;
 
     type FloorPlanner = V1;
 
+    type Params = MyParams;
+
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            params: self.params,
+            table_height: self.table_height,
+            query_height: self.query_height,
+            lookup_table: if self.params.fixed { self.lookup_table.clone() } else { Value::unknown() },
+            value_table: Value::unknown(),
+        }
     }
 
-    fn configure(meta: &mut ConstraintSystem<Field>) -> Self::Config {
-        MyConfig::configure(meta)
+    fn params(&self) -> Self::Params {
+        self.params
     }
 
-    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Field>) -> Result<(), Error> {
-        let theta = layouter.get_challenge(config.theta);
-        let beta = layouter.get_challenge(config.beta);
-        let gamma = layouter.get_challenge(config.gamma);
-        layouter.assign_region(
-            || "lookup value_table in lookup_table", 
-            |mut region| {
-                config.q_first_lookup.enable(&mut region, 0)?;
-                config.q_last_lookup.enable(&mut region, HEIGHT)?;
-                for offset in 0..HEIGHT {
-                    config.q_lookup.enable(&mut region, offset)?;
-                }
-                config.q_first_row_equal.enable(&mut region, 0)?;
-                for offset in 1..HEIGHT {
-                    config.q_other_row_equal.enable(&mut region, offset)?;
-                }
-
-                // First phase
-                for (idx, (&column, values)) in config
-                    .lookup_table
-                    .iter()
-                    .zip(self.lookup_table.transpose_array().iter())
-                    .enumerate()
-                {
-                    for (offset, &value) in values.transpose_array().iter().enumerate() {
-                        region.assign_advice(
-                            || format!("lookup_table[{}][{}]", idx, offset),
-                            column,
-                            offset,
-                            || value,
-                        )?;
-                    }    
-                }
-
-                for (idx, (&column, values)) in config
-                    .value_table
-                    .iter()
-                    .zip(self.value_table.transpose_array().iter())
-                    .enumerate()
-                {
-                    for (offset, &value) in values.transpose_array().iter().enumerate() {
-                        region.assign_advice(
-                            || format!("value_table[{}][{}]", idx, offset),
-                            column,
-                            offset,
-                            || value,
-                        )?;
-                    }
-                }
-
-                for (idx, (&column, values)) in config
-                    .arranged_lookup_table
-                    .iter()
-                    .zip(self.arranged_lookup_table.transpose_array().iter())
-                    .enumerate()
-                {
-                    for (offset, &value) in values.transpose_array().iter().enumerate() {
-                        region.assign_advice(
-                            || format!("arranged_lookup_table[{}][{}]", idx, offset),
-                            column,
-                            offset,
-                            || value,
-                        )?;
-                    }    
-                }
+    // the columns depend on the shape of the circuit, so it is only configured with its parameters
+    fn configure(_meta: &mut ConstraintSystem<Field>) -> Self::Config {
+        unreachable!("MyCircuit is configured by configure_with_params")
+    }
 
-                for (idx, (&column, values)) in config
-                    .arranged_value_table
-                    .iter()
-                    .zip(self.arranged_value_table.transpose_array().iter())
-                    .enumerate()
-                {
-                    for (offset, &value) in values.transpose_array().iter().enumerate() {
-                        region.assign_advice(
-                            || format!("arranged_value_table[{}][{}]", idx, offset),
-                            column,
-                            offset,
-                            || value,
-                        )?;
-                    }
+    fn configure_with_params(meta: &mut ConstraintSystem<Field>, params: Self::Params) -> Self::Config {
+        let lookup_table = (0..params.width)
+            .map(|_| -> Column<Any> {
+                if params.fixed {
+                    meta.fixed_column().into()
+                } else {
+                    meta.advice_column_in(FirstPhase).into()
                 }
+            })
+            .collect();
+        let value_table = (0..params.width).map(|_| meta.advice_column_in(FirstPhase)).collect();
+        if params.logup {
+            MyConfig::LogUp(LogUpChip::configure(meta, lookup_table, value_table))
+        } else {
+            MyConfig::Plookup(LookupChip::configure(meta, lookup_table, value_table))
+        }
+    }
 
-                // Second Phase
-
-                // compute z
-                let mut z = self.lookup_table.zip(self.value_table).zip(self.arranged_lookup_table).zip(self.arranged_value_table).zip(theta).zip(beta).zip(gamma).map(
-                    |((((((lookup_table, value_table), arranged_lookup_table), arranged_value_table), theta), beta), gamma)| {
-                        let mut product_vec = vec![Field::zero(); HEIGHT];
-                        for (index, product) in product_vec.iter_mut().enumerate() {
-                            let (mut compressed_t, mut compressed_v) = (Field::zero(), Field::zero());
-                            for j in 0..WIDTH {
-                                compressed_t *= theta;
-                                compressed_t += arranged_lookup_table[j][index];
-                                compressed_v *= theta;
-                                compressed_v += arranged_value_table[j][index];
-                            }
-                            *product = (compressed_t + gamma) * (compressed_v + beta);
-                        }
-                        product_vec.iter_mut().batch_invert();
-                        for (index, product) in product_vec.iter_mut().enumerate() {
-                            let (mut compressed_t, mut compressed_v) = (Field::zero(), Field::zero());
-                            for j in 0..WIDTH {
-                                compressed_t *= theta;
-                                compressed_t += lookup_table[j][index];
-                                compressed_v *= theta;
-                                compressed_v += value_table[j][index];
-                            }
-                            *product *= (compressed_t + gamma) * (compressed_v + beta);
-                        }
-                        let z = iter::once(Field::one())
-                            .chain(product_vec)
-                            .scan(Field::one(), |state, cur| {
-                                *state *= &cur;
-                                Some(*state)
-                            })
-                            .collect::<Vec<_>>();
-                        
-                        z
-                    }
-                );
-                for (offset, value) in z.transpose_vec(HEIGHT + 1).into_iter().enumerate() {
-                    region.assign_advice(
-                        || format!("z[{}]", offset),
-                        config.z_lookup,
-                        offset,
-                        || value,
-                    )?;
-                }
-                Ok(())
-            }
-        )
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Field>) -> Result<(), Error> {
+        let lookup_table = self.lookup_table.clone();
+        let value_table = self.value_table.clone();
+        match config {
+            MyConfig::Plookup(config) => LookupChip::construct(config).assign(
+                layouter,
+                self.table_height,
+                self.query_height,
+                lookup_table,
+                value_table,
+            ),
+            MyConfig::LogUp(config) => LogUpChip::construct(config).assign(
+                layouter,
+                self.table_height,
+                self.query_height,
+                lookup_table,
+                value_table,
+            ),
+        }
     }
 }
 
-fn test_mock_prover<Field: FieldExt, const WIDTH: usize, const HEIGHT: usize>(
+fn test_mock_prover<Field: FieldExt, ConcreteCircuit: Circuit<Field>>(
     k: u32,
-    circuit: MyCircuit<Field, WIDTH, HEIGHT>,
+    circuit: ConcreteCircuit,
     expected: Result<(), Vec<(metadata::Constraint, FailureLocation)>>,
 ) {
     let prover = MockProver::run::<_>(k, &circuit, vec![]).unwrap();
@@ -481,91 +301,140 @@ fn test_mock_prover<Field: FieldExt, const WIDTH: usize, const HEIGHT: usize>(
     };
 }
 
-fn test_prover<C: CurveAffine, const WIDTH: usize, const HEIGHT: usize>(
-    k: u32,
-    circuit: MyCircuit<C::Scalar, WIDTH, HEIGHT>,
-    expected: bool,
-) {
-    let params = ParamsIPA::<C>::new(k);
-    let vk = keygen_vk(&params, &circuit).unwrap();
-    let pk = keygen_pk(&params, vk, &circuit).unwrap();
-
-    let proof = {
-        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-
-        create_proof::<IPACommitmentScheme<C>, ProverIPA<C>, _, _, _, _>(
-            &params,
-            &pk,
-            &[circuit],
-            &[&[]],
-            OsRng,
-            &mut transcript,
-        )
-        .expect("proof generation should not fail");
-
-        transcript.finalize()
-    };
-
-    let accepted = {
-        let strategy = AccumulatorStrategy::new(&params);
-        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-
-        verify_proof::<IPACommitmentScheme<C>, VerifierIPA<C>, _, _, _>(
-            &params,
-            pk.get_vk(),
-            strategy,
-            &[&[]],
-            &mut transcript,
-        )
-        .map(|strategy| strategy.finalize())
-        .unwrap_or_default()
-    };
-
-    assert_eq!(accepted, expected);
+// prove and verify the circuit with halo2 and the IPA commitment scheme, and report the proof
+fn prove<C: CurveAffine, ConcreteCircuit: Circuit<C::Scalar>>(k: u32, circuit: ConcreteCircuit) {
+    let system = Halo2Ipa::<C, ConcreteCircuit>::new(k);
+    let report = backend::run(Backend::Halo2, &system, circuit, &Vec::new()).expect("proof generation should not fail");
+    assert!(report.verified, "the proof should verify");
+    println!(
+        "{:?}: proof {} bytes, setup {:?}, prove {:?}, verify {:?}",
+        report.backend, report.proof_size, report.setup_time, report.prove_time, report.verify_time
+    );
 }
 
-fn main() {
-    const WIDTH: usize = 4;
-    const HEIGHT: usize = 5;
-    const K: u32 = 8;
-
-    let circuit = &MyCircuit::<Fp, WIDTH, HEIGHT>::rand(&mut OsRng, 3, 3);
+// prove the lookup of `value_table` in `lookup_table`, both given row by row, in a circuit of
+// 2^k rows, or of the fewest rows that hold the tables
+fn run(params: MyParams, lookup_table: Vec<Vec<Fp>>, value_table: Vec<Vec<Fp>>, k: Option<u32>) {
+    let circuit = &MyCircuit::<Fp>::new(params, lookup_table, value_table);
+    let k = k.unwrap_or_else(|| circuit.k());
+    if let Err(e) = circuit.check_k(k) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 
     println!("Lookup table: ");
-    print_array_table::<Fp, WIDTH, HEIGHT>(&circuit.lookup_table);
-    println!("Arranged lookup table: ");
-    print_array_table::<Fp, WIDTH, HEIGHT>(&circuit.arranged_lookup_table);
-    println!("Arranged value table: ");
-    print_array_table::<Fp, WIDTH, HEIGHT>(&circuit.arranged_value_table);
+    print_column_table::<Fp>(&circuit.lookup_table, circuit.table_height);
     println!("Value table: ");
-    print_array_table::<Fp, WIDTH, HEIGHT>(&circuit.value_table);
+    print_column_table::<Fp>(&circuit.value_table, circuit.query_height);
+    circuit.lookup_table.as_ref().zip(circuit.value_table.as_ref()).map(|(lookup_table, value_table)| {
+        let rows = |table: &Vec<Vec<Fp>>, height: usize| -> Vec<Vec<Fp>> {
+            (0..height).map(|i| table.iter().map(|column| column[i]).collect()).collect()
+        };
+        let (lookup_table, value_table) = pad(rows(lookup_table, circuit.table_height), rows(value_table, circuit.query_height));
+        let (arranged_lookup_table, arranged_value_table) = arrange(&lookup_table, &value_table).unwrap();
+        println!("Arranged lookup table: ");
+        print_vec_table::<Fp>(&arranged_lookup_table);
+        println!("Arranged value table: ");
+        print_vec_table::<Fp>(&arranged_value_table);
+    });
+
+    {
+        test_mock_prover(k, circuit.clone(), Ok(()));
+        prove::<EqAffine, _>(k, circuit.clone());
+    }
 
+    // tables filling the whole circuit, up to the blinding rows
     {
-        test_mock_prover(K, circuit.clone(), Ok(()));
-        test_prover::<EqAffine, WIDTH, HEIGHT>(K, circuit.clone(), true);
+        const K: u32 = 6;
+        let height = MyCircuit::<Fp>::max_height(params, K);
+        let full = MyCircuit::<Fp>::rand(&mut OsRng, params, height, height);
+        test_mock_prover(K, full.clone(), Ok(()));
+        prove::<EqAffine, _>(K, full);
+    }
+}
+
+fn main() {
+    // prove the lookup of the tables in the two given files, or of random tables; with --fixed
+    // the lookup table goes into fixed columns, with --logup the LogUp argument replaces plookup,
+    // and with --k <k> the circuit has 2^k rows instead of the fewest that hold the tables
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let fixed_table = args.iter().any(|arg| arg == "--fixed");
+    let logup = args.iter().any(|arg| arg == "--logup");
+    args.retain(|arg| arg != "--fixed" && arg != "--logup");
+    let k = match args.iter().position(|arg| arg == "--k") {
+        Some(index) if index + 1 < args.len() => {
+            let k = args[index + 1].parse::<u32>().unwrap_or_else(|e| {
+                eprintln!("invalid k {:?}: {}", args[index + 1], e);
+                process::exit(1);
+            });
+            args.drain(index..index + 2);
+            Some(k)
+        }
+        Some(_) => {
+            eprintln!("--k needs a value");
+            process::exit(1);
+        }
+        None => None,
+    };
+    let (lookup_table, value_table) = match args.as_slice() {
+        [lookup_table_path, value_table_path] => read_tables::<Fp>(lookup_table_path, value_table_path)
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            }),
+        [] => {
+            let lookup_table = rand_2d_vector::<Fp, _>(&mut OsRng, 4, 8);
+            let value_table = sample_random_values_from_lookup_table(&lookup_table, &mut OsRng, 4, 3);
+            (lookup_table, value_table)
+        }
+        _ => {
+            eprintln!("usage: main [--fixed] [--logup] [--k <k>] [<lookup table file> <value table file>]");
+            process::exit(1);
+        }
+    };
+    let params = MyParams { width: lookup_table[0].len(), fixed: fixed_table, logup };
+    run(params, lookup_table, value_table, k);
+}
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const K: u32 = 6;
+
+    // tables filling the whole circuit, up to the blinding rows
+    fn fill_circuit(width: usize, fixed: bool, logup: bool) {
+        let params = MyParams { width, fixed, logup };
+        let height = MyCircuit::<Fp>::max_height(params, K);
+        let full = MyCircuit::<Fp>::rand(&mut OsRng, params, height, height);
+        assert_eq!(full.check_k(K), Ok(()));
+        assert_eq!(full.k(), K);
+        test_mock_prover(K, full.clone(), Ok(()));
+        prove::<EqAffine, _>(K, full);
     }
 
-    // #[cfg(not(feature = "sanity-checks"))]
-    // {
-    //     use std::ops::IndexMut;
-
-    //     let mut circuit = circuit.clone();
-    //     circuit.shuffled = circuit.shuffled.map(|mut shuffled| {
-    //         shuffled.index_mut(0).swap(0, 1);
-    //         shuffled
-    //     });
-
-    //     test_mock_prover(
-    //         K,
-    //         circuit.clone(),
-    //         Err(vec![(
-    //             ((1, "z should end with 1").into(), 0, "").into(),
-    //             FailureLocation::InRegion {
-    //                 region: (0, "Shuffle original into shuffled").into(),
-    //                 offset: 32,
-    //             },
-    //         )]),
-    //     );
-    //     test_prover::<EqAffine, WIDTH, HEIGHT>(K, circuit, false);
-    // }
-}
\ No newline at end of file
+    #[test]
+    fn tables_fill_the_circuit() {
+        fill_circuit(2, false, false);
+        fill_circuit(2, true, false);
+        fill_circuit(2, false, true);
+    }
+
+    #[test]
+    fn wide_tables_fill_the_circuit() {
+        fill_circuit(12, false, false);
+        fill_circuit(12, true, true);
+    }
+
+    #[test]
+    fn taller_tables_do_not_fit() {
+        let params = MyParams { width: 2, fixed: false, logup: false };
+        let height = MyCircuit::<Fp>::max_height(params, K) + 1;
+        let circuit = MyCircuit::<Fp>::rand(&mut OsRng, params, height, 1);
+        assert!(circuit.check_k(K).is_err());
+        assert_eq!(circuit.k(), K + 1);
+        assert_eq!(circuit.check_k(K + 1), Ok(()));
+    }
+}
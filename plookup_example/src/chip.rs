@@ -0,0 +1,914 @@
+use ff::{BatchInvert, PrimeField};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region, Value},
+    plonk::*,
+    poly::Rotation,
+};
+use std::{collections::HashMap, fmt, iter, marker::PhantomData};
+
+// Columns, selectors and challenges of the multi-column lookup argument. Rows of the value
+// columns must all appear among the rows of the table columns; both are compressed into one
+// field element per row with the challenge theta. The table columns are advice columns, chosen
+// by the prover, or fixed columns, pinned by the verifying key.
+#[derive(Clone, Debug)]
+pub struct LookupConfig {
+    q_lookup: Selector,
+    q_first_lookup: Selector,
+    q_last_lookup: Selector,
+    q_first_row_equal: Selector,
+    q_other_row_equal: Selector,
+    lookup_table: Vec<Column<Any>>,
+    value_table: Vec<Column<Advice>>,
+    arranged_lookup_table: Vec<Column<Advice>>,
+    arranged_value_table: Vec<Column<Advice>>,
+    theta: Challenge,
+    beta: Challenge,
+    gamma: Challenge,
+    z_lookup: Column<Advice>,
+}
+
+impl LookupConfig {
+    // Number of columns of each table
+    pub fn width(&self) -> usize {
+        self.lookup_table.len()
+    }
+
+    pub fn table_columns(&self) -> &[Column<Any>] {
+        &self.lookup_table
+    }
+
+    // Whether the lookup table is fixed, and so part of the verifying key
+    pub fn is_table_fixed(&self) -> bool {
+        self.lookup_table
+            .iter()
+            .all(|&column| Column::<Fixed>::try_from(column).is_ok())
+    }
+
+    pub fn value_columns(&self) -> &[Column<Advice>] {
+        &self.value_table
+    }
+}
+
+// Chip proving that every row of a value table appears in a lookup table, with the plookup
+// argument over arranged copies of both tables. Every row of the value columns is a query: the
+// arranged value table is a permutation of all of them, so the argument can not skip rows the
+// way a gate would. Lookups of gated input expressions are left to
+// `LogUpChip::configure_vector`.
+pub struct LookupChip<Field: FieldExt> {
+    config: LookupConfig,
+    _marker: PhantomData<Field>,
+}
+
+impl<Field: FieldExt> Chip<Field> for LookupChip<Field> {
+    type Config = LookupConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<Field: FieldExt> LookupChip<Field> {
+    pub fn construct(config: LookupConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    // Tallest tables the chip can assign in a circuit of 2^k rows. The region takes one row more
+    // than the tables, for the last value of z, and halo2 keeps the last `blinding_factors() + 1`
+    // rows of every column for blinding, so `meta` must be the complete constraint system of the
+    // circuit.
+    pub fn max_height(meta: &ConstraintSystem<Field>, k: u32) -> usize {
+        max_height(meta, k)
+    }
+
+    // Configures the lookup of `value_columns` into `table_columns`, which set the width of the
+    // tables; the arranged columns and the grand product column are allocated here. The table
+    // columns are either all advice columns or all fixed columns; with fixed columns the lookup
+    // table must be known when the circuit is synthesized without witnesses.
+    pub fn configure(
+        meta: &mut ConstraintSystem<Field>,
+        table_columns: Vec<Column<Any>>,
+        value_columns: Vec<Column<Advice>>,
+    ) -> LookupConfig {
+        let width = check_table_columns(&table_columns);
+        assert_eq!(
+            value_columns.len(),
+            width,
+            "tables must have the same width"
+        );
+
+        let [q_lookup, q_first_lookup, q_last_lookup, q_first_row_equal, q_other_row_equal] =
+            [(); 5].map(|_| meta.selector());
+        let lookup_table = table_columns;
+        let value_table = value_columns;
+        let arranged_lookup_table: Vec<_> = (0..width)
+            .map(|_| meta.advice_column_in(FirstPhase))
+            .collect();
+        let arranged_value_table: Vec<_> = (0..width)
+            .map(|_| meta.advice_column_in(FirstPhase))
+            .collect();
+        let [theta, beta, gamma] = [(); 3].map(|_| meta.challenge_usable_after(FirstPhase));
+        let z_lookup = meta.advice_column_in(SecondPhase);
+
+        meta.create_gate("z should start with 1", |meta| {
+            let q_first_lookup = meta.query_selector(q_first_lookup);
+            let z_lookup = meta.query_advice(z_lookup, Rotation::cur());
+            let one = Expression::Constant(Field::one());
+            vec![q_first_lookup * (one - z_lookup)]
+        });
+
+        meta.create_gate("z should end with 1", |meta| {
+            let q_last_lookup = meta.query_selector(q_last_lookup);
+            let z_lookup = meta.query_advice(z_lookup, Rotation::cur());
+            let one = Expression::Constant(Field::one());
+
+            vec![q_last_lookup * (one - z_lookup)]
+        });
+
+        meta.create_gate("z should have valid transition", |meta| {
+            let q_lookup = meta.query_selector(q_lookup);
+            let lookup_table = query_columns(meta, &lookup_table, Rotation::cur());
+            let value_table = query_columns(meta, &value_table, Rotation::cur());
+            let arranged_lookup_table =
+                query_columns(meta, &arranged_lookup_table, Rotation::cur());
+            let arranged_value_table = query_columns(meta, &arranged_value_table, Rotation::cur());
+            let [theta, beta, gamma] =
+                [theta, beta, gamma].map(|challenge| meta.query_challenge(challenge));
+            let [z, z_w] = [Rotation::cur(), Rotation::next()]
+                .map(|rotation| meta.query_advice(z_lookup, rotation));
+
+            let lookup_merged = compress(&lookup_table, &theta);
+            let value_merged = compress(&value_table, &theta);
+            let arranged_lookup_merged = compress(&arranged_lookup_table, &theta);
+            let arranged_value_merged = compress(&arranged_value_table, &theta);
+            vec![
+                q_lookup
+                    * (z_w
+                        * (arranged_lookup_merged + gamma.clone())
+                        * (arranged_value_merged + beta.clone())
+                        - z * (lookup_merged + gamma) * (value_merged + beta)),
+            ]
+        });
+
+        meta.create_gate(
+            "first rows of arranged_lookup_table and arranged_value_table are equal",
+            |meta| {
+                let q_first_row_equal = meta.query_selector(q_first_row_equal);
+                let arranged_lookup_table =
+                    query_columns(meta, &arranged_lookup_table, Rotation::cur());
+                let arranged_value_table =
+                    query_columns(meta, &arranged_value_table, Rotation::cur());
+                let theta = meta.query_challenge(theta);
+
+                let arranged_lookup_merged = compress(&arranged_lookup_table, &theta);
+                let arranged_value_merged = compress(&arranged_value_table, &theta);
+                vec![q_first_row_equal * (arranged_lookup_merged - arranged_value_merged)]
+            },
+        );
+
+        meta.create_gate("each of other rows of arranged_value_table is equal to previous row or equal to the same one in arranged_lookup_table", |meta| {
+            let q_other_row_equal = meta.query_selector(q_other_row_equal);
+            let arranged_lookup_table =
+                query_columns(meta, &arranged_lookup_table, Rotation::cur());
+            let [arranged_value_table, prev_arranged_value_table] =
+                [Rotation::cur(), Rotation::prev()].map(|rotation| {
+                    query_columns(meta, &arranged_value_table, rotation)
+                });
+            let theta = meta.query_challenge(theta);
+
+            let arranged_lookup_merged = compress(&arranged_lookup_table, &theta);
+            let arranged_value_merged = compress(&arranged_value_table, &theta);
+            let prev_arranged_value_merged = compress(&prev_arranged_value_table, &theta);
+            vec![
+                q_other_row_equal
+                    * (arranged_lookup_merged - arranged_value_merged.clone())
+                    * (arranged_value_merged - prev_arranged_value_merged),
+            ]
+        });
+
+        LookupConfig {
+            q_lookup,
+            q_first_lookup,
+            q_last_lookup,
+            q_first_row_equal,
+            q_other_row_equal,
+            lookup_table,
+            value_table,
+            arranged_lookup_table,
+            arranged_value_table,
+            theta,
+            beta,
+            gamma,
+            z_lookup,
+        }
+    }
+
+    // Assigns the `table_height` rows of `lookup_table` and the `query_height` rows of
+    // `value_table`, both given column by column, together with their arrangement and the grand
+    // product; every row of `value_table` must be a row of `lookup_table`, otherwise synthesis
+    // fails and [`arrange`] tells which row is missing. The shorter table is padded with the
+    // first row of `lookup_table`, so the region takes one row more than the taller table, which
+    // must be at most [`Self::max_height`] rows high.
+    //
+    // The arranged value table is a permutation of the value table and as tall as the arranged
+    // lookup table, so the plookup argument can not leave value rows out: a large table with a
+    // few queries still costs a value, arranged value and grand product row per table row.
+    // `LogUpChip` gates its queries and has no such limitation.
+    pub fn assign(
+        &self,
+        layouter: impl Layouter<Field>,
+        table_height: usize,
+        query_height: usize,
+        lookup_table: Value<Vec<Vec<Field>>>,
+        value_table: Value<Vec<Vec<Field>>>,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+        let width = config.width();
+        assert!(table_height > 0, "lookup table must have at least one row");
+        let height = table_height.max(query_height);
+        // the lookup table is padded on its own, so that a fixed table is still known when the
+        // circuit is synthesized without witnesses
+        let lookup_table = lookup_table.map(|lookup_table| {
+            let mut lookup_rows = to_rows(&lookup_table, width, table_height);
+            lookup_rows.resize(height, lookup_rows[0].clone());
+            to_columns(&lookup_rows, width)
+        });
+        let value_table =
+            lookup_table
+                .as_ref()
+                .zip(value_table)
+                .map(|(lookup_table, value_table)| {
+                    let padding = lookup_table.iter().map(|column| column[0]).collect();
+                    let mut value_rows = to_rows(&value_table, width, query_height);
+                    value_rows.resize(height, padding);
+                    to_columns(&value_rows, width)
+                });
+        let arranged =
+            lookup_table
+                .as_ref()
+                .zip(value_table.as_ref())
+                .map(|(lookup_table, value_table)| {
+                    arrange(
+                        &to_rows(lookup_table, width, height),
+                        &to_rows(value_table, width, height),
+                    )
+                });
+        // a value missing from the table leaves no valid witness
+        arranged.error_if_known_and(|arranged| arranged.is_err())?;
+        let arranged = arranged.map(|arranged| {
+            let (arranged_lookup_table, arranged_value_table) = arranged.unwrap();
+            (
+                to_columns(&arranged_lookup_table, width),
+                to_columns(&arranged_value_table, width),
+            )
+        });
+        let arranged_lookup_table = arranged.as_ref().map(|(table, _)| table.clone());
+        let arranged_value_table = arranged.map(|(_, table)| table);
+
+        self.assign_arranged(
+            layouter,
+            height,
+            lookup_table,
+            value_table,
+            arranged_lookup_table,
+            arranged_value_table,
+        )
+    }
+
+    // Assigns tables of `height` rows, already padded to the same height, with the arrangement
+    // of both tables as returned by [`arrange`], and computes the grand product. The arrangement
+    // is not checked, so that tests can assign tampered witnesses.
+    pub fn assign_arranged(
+        &self,
+        mut layouter: impl Layouter<Field>,
+        height: usize,
+        lookup_table: Value<Vec<Vec<Field>>>,
+        value_table: Value<Vec<Vec<Field>>>,
+        arranged_lookup_table: Value<Vec<Vec<Field>>>,
+        arranged_value_table: Value<Vec<Vec<Field>>>,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+        let theta = layouter.get_challenge(config.theta);
+        let beta = layouter.get_challenge(config.beta);
+        let gamma = layouter.get_challenge(config.gamma);
+        layouter.assign_region(
+            || "lookup value_table in lookup_table",
+            |mut region| {
+                config.q_first_lookup.enable(&mut region, 0)?;
+                config.q_last_lookup.enable(&mut region, height)?;
+                for offset in 0..height {
+                    config.q_lookup.enable(&mut region, offset)?;
+                }
+                config.q_first_row_equal.enable(&mut region, 0)?;
+                for offset in 1..height {
+                    config.q_other_row_equal.enable(&mut region, offset)?;
+                }
+
+                // First phase
+                for (idx, &column) in config.lookup_table.iter().enumerate() {
+                    for offset in 0..height {
+                        assign_table_cell(
+                            &mut region,
+                            || format!("lookup_table[{}][{}]", idx, offset),
+                            column,
+                            offset,
+                            lookup_table.as_ref().map(|table| table[idx][offset]),
+                        )?;
+                    }
+                }
+                for (name, columns, table) in [
+                    ("value_table", &config.value_table, &value_table),
+                    (
+                        "arranged_lookup_table",
+                        &config.arranged_lookup_table,
+                        &arranged_lookup_table,
+                    ),
+                    (
+                        "arranged_value_table",
+                        &config.arranged_value_table,
+                        &arranged_value_table,
+                    ),
+                ] {
+                    for (idx, &column) in columns.iter().enumerate() {
+                        for offset in 0..height {
+                            region.assign_advice(
+                                || format!("{}[{}][{}]", name, idx, offset),
+                                column,
+                                offset,
+                                || table.as_ref().map(|table| table[idx][offset]),
+                            )?;
+                        }
+                    }
+                }
+
+                // Second Phase
+
+                // compute z
+                let z = lookup_table
+                    .as_ref()
+                    .zip(value_table.as_ref())
+                    .zip(
+                        arranged_lookup_table
+                            .as_ref()
+                            .zip(arranged_value_table.as_ref()),
+                    )
+                    .zip(theta.zip(beta).zip(gamma))
+                    .map(
+                        |(
+                            (
+                                (lookup_table, value_table),
+                                (arranged_lookup_table, arranged_value_table),
+                            ),
+                            ((theta, beta), gamma),
+                        )| {
+                            let compress_row = |table: &[Vec<Field>], index: usize| {
+                                table
+                                    .iter()
+                                    .fold(Field::zero(), |acc, column| acc * theta + column[index])
+                            };
+                            let mut product_vec = (0..height)
+                                .map(|index| {
+                                    (compress_row(arranged_lookup_table, index) + gamma)
+                                        * (compress_row(arranged_value_table, index) + beta)
+                                })
+                                .collect::<Vec<_>>();
+                            product_vec.iter_mut().batch_invert();
+                            for (index, product) in product_vec.iter_mut().enumerate() {
+                                *product *= (compress_row(lookup_table, index) + gamma)
+                                    * (compress_row(value_table, index) + beta);
+                            }
+                            iter::once(Field::one())
+                                .chain(product_vec)
+                                .scan(Field::one(), |state, cur| {
+                                    *state *= &cur;
+                                    Some(*state)
+                                })
+                                .collect::<Vec<_>>()
+                        },
+                    );
+                for (offset, value) in z.transpose_vec(height + 1).into_iter().enumerate() {
+                    region.assign_advice(
+                        || format!("z[{}]", offset),
+                        config.z_lookup,
+                        offset,
+                        || value,
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+// Tallest tables that fit in 2^k rows when the region takes one row more than the tables:
+// halo2 keeps the last `blinding_factors() + 1` rows of every column of `meta` for blinding
+pub(crate) fn max_height<Field: FieldExt>(meta: &ConstraintSystem<Field>, k: u32) -> usize {
+    (1usize << k).saturating_sub(meta.blinding_factors() + 2)
+}
+
+// Checks that table columns are all advice or all fixed columns, returning their number
+pub(crate) fn check_table_columns(table_columns: &[Column<Any>]) -> usize {
+    let width = table_columns.len();
+    assert!(width > 0, "tables must have at least one column");
+    let fixed = table_columns
+        .iter()
+        .filter(|&&column| Column::<Fixed>::try_from(column).is_ok())
+        .count();
+    let advice = table_columns
+        .iter()
+        .filter(|&&column| Column::<Advice>::try_from(column).is_ok())
+        .count();
+    assert!(
+        fixed == width || advice == width,
+        "table columns must be all advice or all fixed columns"
+    );
+    width
+}
+
+// Assigns a cell of a table column, be it an advice or a fixed column
+pub(crate) fn assign_table_cell<Field: FieldExt, A: Fn() -> String>(
+    region: &mut Region<'_, Field>,
+    annotation: A,
+    column: Column<Any>,
+    offset: usize,
+    value: Value<Field>,
+) -> Result<(), Error> {
+    match Column::<Fixed>::try_from(column) {
+        Ok(column) => {
+            region.assign_fixed(annotation, column, offset, || value)?;
+        }
+        Err(_) => {
+            let column = Column::<Advice>::try_from(column).unwrap();
+            region.assign_advice(annotation, column, offset, || value)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn query_columns<Field: FieldExt, C: Into<Column<Any>> + Copy>(
+    meta: &mut VirtualCells<'_, Field>,
+    columns: &[C],
+    rotation: Rotation,
+) -> Vec<Expression<Field>> {
+    columns
+        .iter()
+        .map(|&column| meta.query_any(column, rotation))
+        .collect()
+}
+
+// Compresses one row of expressions into a single expression, theta acting as the base
+pub(crate) fn compress<Field: FieldExt>(
+    row: &[Expression<Field>],
+    theta: &Expression<Field>,
+) -> Expression<Field> {
+    row.iter()
+        .cloned()
+        .reduce(|acc, v| acc * theta.clone() + v)
+        .unwrap()
+}
+
+// Turns a table given column by column into its rows, checking it has the expected shape
+pub(crate) fn to_rows<Field: FieldExt>(
+    table: &[Vec<Field>],
+    width: usize,
+    height: usize,
+) -> Vec<Vec<Field>> {
+    assert_eq!(table.len(), width, "table must have {} columns", width);
+    for column in table {
+        assert_eq!(
+            column.len(),
+            height,
+            "table columns must have {} rows",
+            height
+        );
+    }
+    (0..height)
+        .map(|i| table.iter().map(|column| column[i]).collect())
+        .collect()
+}
+
+pub(crate) fn to_columns<Field: FieldExt>(rows: &[Vec<Field>], width: usize) -> Vec<Vec<Field>> {
+    (0..width)
+        .map(|j| rows.iter().map(|row| row[j]).collect())
+        .collect()
+}
+
+// Pads tables given row by row to the height of the taller one, with the first row of
+// `lookup_table`: any row of the lookup table is a valid padding for both tables. Panics when
+// `lookup_table` is empty, as nothing can be looked up in it.
+pub fn pad<Field: FieldExt>(
+    mut lookup_table: Vec<Vec<Field>>,
+    mut value_table: Vec<Vec<Field>>,
+) -> (Vec<Vec<Field>>, Vec<Vec<Field>>) {
+    assert!(
+        !lookup_table.is_empty(),
+        "lookup table must have at least one row"
+    );
+    let height = lookup_table.len().max(value_table.len());
+    let padding = lookup_table[0].clone();
+    lookup_table.resize(height, padding.clone());
+    value_table.resize(height, padding);
+    (lookup_table, value_table)
+}
+
+// Error of the lookup witness generator
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupError {
+    // Row `index` of the value table is not a row of the lookup table
+    MissingRow { index: usize },
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingRow { index } => {
+                write!(
+                    f,
+                    "row {} of the value table is not in the lookup table",
+                    index
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+// Returns the arranged copies of `lookup_table` and `value_table`, given row by row and of the
+// same height: equal value rows are grouped, in order of first occurrence, and the first row of
+// each group is matched by the same row of the lookup table, the unused lookup rows filling the
+// remaining places. Rows are grouped by their encoding, so no order on field elements is needed.
+pub fn arrange<Field: FieldExt>(
+    lookup_table: &[Vec<Field>],
+    value_table: &[Vec<Field>],
+) -> Result<(Vec<Vec<Field>>, Vec<Vec<Field>>), LookupError> {
+    let height = lookup_table.len();
+    assert_eq!(
+        value_table.len(),
+        height,
+        "tables must have the same height"
+    );
+
+    // group the value rows, remembering the first row of each group
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    let mut group_of: HashMap<Vec<u8>, usize> = HashMap::new();
+    for (index, row) in value_table.iter().enumerate() {
+        let group = *group_of.entry(row_key(row)).or_insert_with(|| {
+            groups.push((index, 0));
+            groups.len() - 1
+        });
+        groups[group].1 += 1;
+    }
+
+    // match each group with a row of the lookup table
+    let mut lookup_rows: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+    for (index, row) in lookup_table.iter().enumerate().rev() {
+        lookup_rows.entry(row_key(row)).or_default().push(index);
+    }
+    let mut selected_rows = vec![false; height];
+    for &(first, _) in groups.iter() {
+        let index = lookup_rows
+            .get_mut(&row_key(&value_table[first]))
+            .and_then(|indices| indices.pop())
+            .ok_or(LookupError::MissingRow { index: first })?;
+        selected_rows[index] = true;
+    }
+
+    let mut unused_rows = lookup_table
+        .iter()
+        .zip(selected_rows)
+        .filter(|(_, selected)| !selected)
+        .map(|(row, _)| row);
+    let mut arranged_lookup_table = Vec::with_capacity(height);
+    let mut arranged_value_table = Vec::with_capacity(height);
+    for (first, count) in groups {
+        let row = &value_table[first];
+        arranged_lookup_table.push(row.clone());
+        arranged_lookup_table.extend(unused_rows.by_ref().take(count - 1).cloned());
+        arranged_value_table.extend(iter::repeat(row).take(count).cloned());
+    }
+
+    Ok((arranged_lookup_table, arranged_value_table))
+}
+
+pub(crate) fn row_key<Field: FieldExt>(row: &[Field]) -> Vec<u8> {
+    row.iter()
+        .flat_map(|element| element.to_repr().as_ref().to_vec())
+        .collect()
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ipa::Halo2Ipa;
+    use halo2_proofs::{
+        arithmetic::CurveAffine,
+        circuit::floor_planner::V1,
+        dev::{MockProver, VerifyFailure},
+    };
+    use halo2curves::pasta::{EqAffine, Fp};
+    use proptest::prelude::*;
+    use vrt::backend::ProofSystem;
+
+    const WIDTH: usize = 2;
+    const K: u32 = 6;
+
+    const OTHER_ROWS_GATE: &str = "each of other rows of arranged_value_table is equal to previous row or equal to the same one in arranged_lookup_table";
+    const FIRST_ROW_GATE: &str =
+        "first rows of arranged_lookup_table and arranged_value_table are equal";
+    const Z_END_GATE: &str = "z should end with 1";
+
+    // generates the keys, proves and verifies the circuit
+    pub(crate) fn test_prover<C: CurveAffine, ConcreteCircuit: Circuit<C::Scalar>>(
+        k: u32,
+        circuit: ConcreteCircuit,
+        expected: bool,
+    ) {
+        let system = Halo2Ipa::<C, ConcreteCircuit>::new(k);
+        let (pk, vk) = system.setup(&circuit).unwrap();
+        let proof = system
+            .prove(&pk, circuit, &Vec::new())
+            .expect("proof generation should not fail");
+        let accepted = system.verify(&vk, &Vec::new(), &proof).unwrap();
+
+        assert_eq!(accepted, expected);
+    }
+
+    // Tables given column by column; FIXED puts the lookup table in fixed columns, which keep
+    // their values without witnesses
+    #[derive(Clone)]
+    struct TableCircuit<const FIXED: bool> {
+        table_height: usize,
+        query_height: usize,
+        lookup_table: Value<Vec<Vec<Fp>>>,
+        value_table: Value<Vec<Vec<Fp>>>,
+    }
+
+    impl<const FIXED: bool> TableCircuit<FIXED> {
+        fn new(lookup_table: &[[u64; WIDTH]], value_table: &[[u64; WIDTH]]) -> Self {
+            let columns = |rows: &[[u64; WIDTH]]| -> Value<Vec<Vec<Fp>>> {
+                Value::known(
+                    (0..WIDTH)
+                        .map(|j| rows.iter().map(|row| Fp::from(row[j])).collect())
+                        .collect(),
+                )
+            };
+            Self {
+                table_height: lookup_table.len(),
+                query_height: value_table.len(),
+                lookup_table: columns(lookup_table),
+                value_table: columns(value_table),
+            }
+        }
+    }
+
+    impl<const FIXED: bool> Circuit<Fp> for TableCircuit<FIXED> {
+        type Config = LookupConfig;
+        type FloorPlanner = V1;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                lookup_table: if FIXED {
+                    self.lookup_table.clone()
+                } else {
+                    Value::unknown()
+                },
+                value_table: Value::unknown(),
+                ..self.clone()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let lookup_table = (0..WIDTH)
+                .map(|_| -> Column<Any> {
+                    if FIXED {
+                        meta.fixed_column().into()
+                    } else {
+                        meta.advice_column_in(FirstPhase).into()
+                    }
+                })
+                .collect();
+            let value_table = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase))
+                .collect();
+            LookupChip::configure(meta, lookup_table, value_table)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            LookupChip::construct(config).assign(
+                layouter,
+                self.table_height,
+                self.query_height,
+                self.lookup_table.clone(),
+                self.value_table.clone(),
+            )
+        }
+    }
+
+    // Circuit assigning the tables and their arrangement exactly as given, row by row, so that
+    // witnesses can be tampered with
+    #[derive(Clone)]
+    struct TamperedCircuit {
+        lookup_table: Vec<Vec<Fp>>,
+        value_table: Vec<Vec<Fp>>,
+        arranged_lookup_table: Vec<Vec<Fp>>,
+        arranged_value_table: Vec<Vec<Fp>>,
+    }
+
+    impl TamperedCircuit {
+        // honest witnesses for a lookup table of distinct rows and values picked from it
+        fn honest(rows: Vec<(u64, u64)>, picks: Vec<usize>) -> Self {
+            let lookup_table: Vec<Vec<Fp>> = rows
+                .iter()
+                .map(|&(a, b)| vec![Fp::from(a), Fp::from(b)])
+                .collect();
+            let value_table = picks
+                .iter()
+                .map(|&pick| lookup_table[pick % lookup_table.len()].clone())
+                .collect();
+            let (lookup_table, value_table) = pad(lookup_table, value_table);
+            let (arranged_lookup_table, arranged_value_table) =
+                arrange(&lookup_table, &value_table).unwrap();
+            Self {
+                lookup_table,
+                value_table,
+                arranged_lookup_table,
+                arranged_value_table,
+            }
+        }
+    }
+
+    impl Circuit<Fp> for TamperedCircuit {
+        type Config = LookupConfig;
+        type FloorPlanner = V1;
+        type Params = ();
+
+        // the tables are needed by the key generation of test_prover, which is the only caller
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let lookup_table = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase).into())
+                .collect();
+            let value_table = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase))
+                .collect();
+            LookupChip::configure(meta, lookup_table, value_table)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let columns = |rows: &Vec<Vec<Fp>>| -> Value<Vec<Vec<Fp>>> {
+                Value::known(to_columns(rows, WIDTH))
+            };
+            LookupChip::construct(config).assign_arranged(
+                layouter,
+                self.lookup_table.len(),
+                columns(&self.lookup_table),
+                columns(&self.value_table),
+                columns(&self.arranged_lookup_table),
+                columns(&self.arranged_value_table),
+            )
+        }
+    }
+
+    // check that MockProver reports only unsatisfied constraints, one of them in `gate`, and
+    // that a proof of the circuit of 2^k rows is rejected
+    pub(crate) fn assert_rejected<ConcreteCircuit: Circuit<Fp>>(
+        k: u32,
+        circuit: ConcreteCircuit,
+        gate: &str,
+    ) {
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        let failures = prover
+            .verify()
+            .expect_err("tampered witnesses must not satisfy the circuit");
+        let gates: Vec<String> = failures
+            .into_iter()
+            .map(|failure| match failure {
+                VerifyFailure::ConstraintNotSatisfied { constraint, .. } => constraint.to_string(),
+                failure => panic!("unexpected failure {:?}", failure),
+            })
+            .collect();
+        assert!(
+            gates
+                .iter()
+                .any(|name| name.contains(&format!("('{}')", gate))),
+            "expected a failure of gate {:?}, got {:?}",
+            gate,
+            gates
+        );
+        test_prover::<EqAffine, _>(k, circuit, false);
+    }
+
+    // distinct table rows and at most as many values, so that only the value table is padded
+    fn tables() -> impl Strategy<Value = (Vec<(u64, u64)>, Vec<usize>)> {
+        prop::collection::btree_set(any::<(u64, u64)>(), 2..8).prop_flat_map(|rows| {
+            let height = rows.len();
+            (
+                Just(rows.into_iter().collect()),
+                prop::collection::vec(any::<usize>(), 1..=height),
+            )
+        })
+    }
+
+    #[test]
+    fn arrange_reports_missing_row() {
+        let row = |a: u64, b: u64| vec![Fp::from(a), Fp::from(b)];
+        let lookup_table = vec![row(1, 2), row(3, 4), row(5, 6)];
+
+        let (arranged_lookup_table, arranged_value_table) =
+            arrange(&lookup_table, &[row(5, 6), row(1, 2), row(5, 6)]).unwrap();
+        assert_eq!(arranged_value_table, vec![row(5, 6), row(5, 6), row(1, 2)]);
+        assert_eq!(arranged_lookup_table, vec![row(5, 6), row(3, 4), row(1, 2)]);
+
+        let err = arrange(&lookup_table, &[row(1, 2), row(2, 1), row(3, 4)]).unwrap_err();
+        assert_eq!(err, LookupError::MissingRow { index: 1 });
+    }
+
+    #[test]
+    fn fixed_table_survives_keygen() {
+        let circuit =
+            TableCircuit::<true>::new(&[[1, 2], [3, 4], [5, 6], [7, 8]], &[[5, 6], [1, 2], [5, 6]]);
+        let mut meta = ConstraintSystem::<Fp>::default();
+        assert!(TableCircuit::<true>::configure(&mut meta).is_table_fixed());
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+        test_prover::<EqAffine, _>(K, circuit, true);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4))]
+
+        #[test]
+        fn honest_witnesses_are_accepted((rows, picks) in tables()) {
+            let circuit = TamperedCircuit::honest(rows, picks);
+            let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+            prop_assert_eq!(prover.verify(), Ok(()));
+            test_prover::<EqAffine, _>(K, circuit, true);
+        }
+
+        #[test]
+        fn honest_fixed_table_is_accepted((rows, picks) in tables()) {
+            let lookup_table: Vec<[u64; WIDTH]> = rows.iter().map(|&(a, b)| [a, b]).collect();
+            let value_table: Vec<[u64; WIDTH]> = picks
+                .iter()
+                .map(|&pick| lookup_table[pick % lookup_table.len()])
+                .collect();
+            let circuit = TableCircuit::<true>::new(&lookup_table, &value_table);
+            let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+            prop_assert_eq!(prover.verify(), Ok(()));
+            test_prover::<EqAffine, _>(K, circuit, true);
+        }
+
+        #[test]
+        fn value_missing_from_table_is_rejected((rows, picks) in tables(), index in any::<prop::sample::Index>()) {
+            let mut circuit = TamperedCircuit::honest(rows, picks);
+            // no row of the table holds p - 1, as table entries are 64 bits long
+            let index = index.index(circuit.value_table.len());
+            circuit.value_table[index] = vec![-Fp::from(1); WIDTH];
+            assert_rejected(K, circuit, Z_END_GATE);
+        }
+
+        #[test]
+        fn swapped_arranged_values_are_rejected((rows, picks) in tables(), i in any::<prop::sample::Index>(), j in any::<prop::sample::Index>()) {
+            let mut circuit = TamperedCircuit::honest(rows, picks);
+            let height = circuit.arranged_value_table.len();
+            let (i, j) = (1 + i.index(height - 1), 1 + j.index(height - 1));
+            prop_assume!(circuit.arranged_value_table[i] != circuit.arranged_value_table[j]);
+            // the grand product still holds, but equal arranged values are no longer grouped
+            circuit.arranged_value_table.swap(i, j);
+            assert_rejected(K, circuit, OTHER_ROWS_GATE);
+        }
+
+        #[test]
+        fn broken_first_row_is_rejected((rows, picks) in tables(), j in any::<prop::sample::Index>()) {
+            let mut circuit = TamperedCircuit::honest(rows, picks);
+            let j = 1 + j.index(circuit.arranged_lookup_table.len() - 1);
+            prop_assume!(circuit.arranged_lookup_table[0] != circuit.arranged_lookup_table[j]);
+            circuit.arranged_lookup_table.swap(0, j);
+            assert_rejected(K, circuit, FIRST_ROW_GATE);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use halo2_proofs::{
+    arithmetic::CurveAffine,
+    plonk::*,
+    poly::{
+        commitment::ParamsProver,
+        ipa::{
+            commitment::{IPACommitmentScheme, ParamsIPA},
+            multiopen::{ProverIPA, VerifierIPA},
+            strategy::AccumulatorStrategy,
+        },
+        VerificationStrategy,
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use rand_core::OsRng;
+use std::marker::PhantomData;
+use vrt::backend::{ProofSystem, ProofSystemError};
+
+// Halo2 with the IPA commitment scheme, implementing the proving interface shared with the
+// Groth16 and STARK backends of the verifiable runtime; `vrt::backend::run` reports it as
+// `Backend::Halo2`
+pub struct Halo2Ipa<C: CurveAffine, ConcreteCircuit> {
+    params: ParamsIPA<C>,
+    _marker: PhantomData<ConcreteCircuit>,
+}
+
+impl<C: CurveAffine, ConcreteCircuit> Halo2Ipa<C, ConcreteCircuit> {
+    pub fn new(k: u32) -> Self {
+        Self {
+            params: ParamsIPA::<C>::new(k),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: CurveAffine, ConcreteCircuit: Circuit<C::Scalar>> ProofSystem
+    for Halo2Ipa<C, ConcreteCircuit>
+{
+    type Circuit = ConcreteCircuit;
+    // one vector of values per instance column
+    type PublicInputs = Vec<Vec<C::Scalar>>;
+    type ProvingKey = ProvingKey<C>;
+    type VerifyingKey = VerifyingKey<C>;
+    // halo2 proofs are already the serialized transcript
+    type Proof = Vec<u8>;
+
+    fn setup(
+        &self,
+        circuit: &Self::Circuit,
+    ) -> Result<(Self::ProvingKey, Self::VerifyingKey), ProofSystemError> {
+        // the keys are generated as a verifier would, from the circuit without witnesses
+        let circuit = circuit.without_witnesses();
+        let setup_error = |e: Error| ProofSystemError::Setup(format!("{:?}", e));
+        let vk = keygen_vk(&self.params, &circuit).map_err(setup_error)?;
+        let pk = keygen_pk(&self.params, vk.clone(), &circuit).map_err(setup_error)?;
+        Ok((pk, vk))
+    }
+
+    fn prove(
+        &self,
+        pk: &Self::ProvingKey,
+        circuit: Self::Circuit,
+        public_inputs: &Self::PublicInputs,
+    ) -> Result<Self::Proof, ProofSystemError> {
+        let instances = public_inputs
+            .iter()
+            .map(|column| column.as_slice())
+            .collect::<Vec<_>>();
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof::<IPACommitmentScheme<C>, ProverIPA<C>, _, _, _, _>(
+            &self.params,
+            pk,
+            &[circuit],
+            &[instances.as_slice()],
+            OsRng,
+            &mut transcript,
+        )
+        .map_err(|e| ProofSystemError::Prover(format!("{:?}", e)))?;
+
+        Ok(transcript.finalize())
+    }
+
+    fn verify(
+        &self,
+        vk: &Self::VerifyingKey,
+        public_inputs: &Self::PublicInputs,
+        proof: &Self::Proof,
+    ) -> Result<bool, ProofSystemError> {
+        let instances = public_inputs
+            .iter()
+            .map(|column| column.as_slice())
+            .collect::<Vec<_>>();
+        let strategy = AccumulatorStrategy::new(&self.params);
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        Ok(
+            verify_proof::<IPACommitmentScheme<C>, VerifierIPA<C>, _, _, _>(
+                &self.params,
+                vk,
+                strategy,
+                &[instances.as_slice()],
+                &mut transcript,
+            )
+            .map(|strategy| strategy.finalize())
+            .unwrap_or_default(),
+        )
+    }
+
+    fn serialize_proof(&self, proof: &Self::Proof) -> Result<Vec<u8>, ProofSystemError> {
+        Ok(proof.clone())
+    }
+}
@@ -0,0 +1,12 @@
+// Multi-column plookup, LogUp and shuffle arguments as reusable halo2 chips, and the IPA proof
+// system used to prove circuits built with them
+
+mod chip;
+mod ipa;
+mod logup;
+mod shuffle;
+
+pub use chip::{arrange, pad, LookupChip, LookupConfig, LookupError};
+pub use ipa::Halo2Ipa;
+pub use logup::{multiplicities, LogUpChip, LogUpConfig};
+pub use shuffle::{ShuffleChip, ShuffleConfig};
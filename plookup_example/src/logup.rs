@@ -0,0 +1,746 @@
+use crate::chip::{
+    assign_table_cell, check_table_columns, compress, max_height, query_columns, row_key,
+    to_columns, to_rows, LookupError,
+};
+use ff::BatchInvert;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region, Value},
+    plonk::*,
+    poly::Rotation,
+};
+use std::{collections::HashMap, iter, marker::PhantomData};
+
+// Columns, selectors and challenges of the multi-column LogUp argument: the rows of the value
+// columns appear among the rows of the table columns when
+//     sum_i 1 / (beta + value_i) = sum_j m_j / (beta + table_j)
+// where m_j is the number of times row j of the table is looked up. Rows are compressed with
+// the challenge theta, and the running sum phi checks the equality row by row. Unlike the
+// plookup argument, no arranged copies of the tables are needed, and the looked up rows can be
+// any expressions gated per row, see `LogUpChip::configure_vector`.
+#[derive(Clone, Debug)]
+pub struct LogUpConfig {
+    q_lookup: Selector,
+    // Rows of the value columns that are queries, set by `LogUpChip::configure` only
+    q_value: Option<Selector>,
+    q_first_lookup: Selector,
+    q_last_lookup: Selector,
+    lookup_table: Vec<Column<Any>>,
+    value_table: Vec<Column<Advice>>,
+    multiplicities: Column<Advice>,
+    theta: Challenge,
+    beta: Challenge,
+    phi: Column<Advice>,
+}
+
+impl LogUpConfig {
+    // Number of columns of each table
+    pub fn width(&self) -> usize {
+        self.lookup_table.len()
+    }
+
+    pub fn table_columns(&self) -> &[Column<Any>] {
+        &self.lookup_table
+    }
+
+    // Value columns, empty when the chip looks up expressions
+    pub fn value_columns(&self) -> &[Column<Advice>] {
+        &self.value_table
+    }
+}
+
+// Chip proving that every row of a value table appears in a lookup table, with the LogUp
+// argument over multiplicities of the table rows
+pub struct LogUpChip<Field: FieldExt> {
+    config: LogUpConfig,
+    _marker: PhantomData<Field>,
+}
+
+impl<Field: FieldExt> Chip<Field> for LogUpChip<Field> {
+    type Config = LogUpConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<Field: FieldExt> LogUpChip<Field> {
+    pub fn construct(config: LogUpConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    // Tallest tables the chip can assign in a circuit of 2^k rows, see `LookupChip::max_height`
+    pub fn max_height(meta: &ConstraintSystem<Field>, k: u32) -> usize {
+        max_height(meta, k)
+    }
+
+    // Configures the lookup of `value_columns` into `table_columns`, like
+    // `LookupChip::configure`; the multiplicity column and the running sum column are allocated
+    // here
+    pub fn configure(
+        meta: &mut ConstraintSystem<Field>,
+        table_columns: Vec<Column<Any>>,
+        value_columns: Vec<Column<Advice>>,
+    ) -> LogUpConfig {
+        assert_eq!(
+            value_columns.len(),
+            table_columns.len(),
+            "tables must have the same width"
+        );
+        // the selector is the gate of the queries, so it must take no other value than 0 and 1:
+        // simple selectors may be combined into a column taking other values
+        let q_value = meta.complex_selector();
+        let mut config = Self::configure_vector(meta, |meta| {
+            let inputs = value_columns
+                .iter()
+                .zip(table_columns.iter())
+                .map(|(&value, &table)| (meta.query_advice(value, Rotation::cur()), table))
+                .collect();
+            (meta.query_selector(q_value), inputs)
+        });
+        config.q_value = Some(q_value);
+        config.value_table = value_columns;
+        config
+    }
+
+    // Configures a lookup of input expressions into table columns, in the style of halo2's
+    // `meta.lookup`: `queries` returns a gate expression and pairs of an input expression and the
+    // table column it is looked up in. Each row of the region is a query when the gate evaluates
+    // to 1, and is ignored when it evaluates to 0, so one table can serve many gates; the gate is
+    // constrained to be boolean, e.g. a selector or a flag column, and must vanish on rows the
+    // caller leaves unassigned. Inputs are compressed with theta like table rows.
+    pub fn configure_vector(
+        meta: &mut ConstraintSystem<Field>,
+        queries: impl FnOnce(
+            &mut VirtualCells<'_, Field>,
+        ) -> (Expression<Field>, Vec<(Expression<Field>, Column<Any>)>),
+    ) -> LogUpConfig {
+        let [q_lookup, q_first_lookup, q_last_lookup] = [(); 3].map(|_| meta.selector());
+        let multiplicities = meta.advice_column_in(FirstPhase);
+        let [theta, beta] = [(); 2].map(|_| meta.challenge_usable_after(FirstPhase));
+        let phi = meta.advice_column_in(SecondPhase);
+
+        meta.create_gate("phi should start with 0", |meta| {
+            let q_first_lookup = meta.query_selector(q_first_lookup);
+            let phi = meta.query_advice(phi, Rotation::cur());
+            vec![q_first_lookup * phi]
+        });
+
+        meta.create_gate("phi should end with 0", |meta| {
+            let q_last_lookup = meta.query_selector(q_last_lookup);
+            let phi = meta.query_advice(phi, Rotation::cur());
+            vec![q_last_lookup * phi]
+        });
+
+        let mut lookup_table = Vec::new();
+        let mut gate_expression = None;
+        meta.create_gate("phi should have valid transition", |meta| {
+            let q_lookup = meta.query_selector(q_lookup);
+            let (gate, inputs) = queries(meta);
+            gate_expression = Some(gate.clone());
+            let (inputs, columns): (Vec<_>, Vec<_>) = inputs.into_iter().unzip();
+            lookup_table = columns;
+            let table = query_columns(meta, &lookup_table, Rotation::cur());
+            let multiplicity = meta.query_advice(multiplicities, Rotation::cur());
+            let [theta, beta] = [theta, beta].map(|challenge| meta.query_challenge(challenge));
+            let [phi, phi_w] = [Rotation::cur(), Rotation::next()]
+                .map(|rotation| meta.query_advice(phi, rotation));
+
+            // phi_w - phi = gate / (beta + input) - multiplicity / (beta + table)
+            let lookup_merged = compress(&table, &theta) + beta.clone();
+            let input_merged = compress(&inputs, &theta) + beta;
+            vec![
+                q_lookup
+                    * ((phi_w - phi) * input_merged.clone() * lookup_merged.clone()
+                        - (gate * lookup_merged - multiplicity * input_merged)),
+            ]
+        });
+        check_table_columns(&lookup_table);
+
+        // the gate weighs the inputs of its row in the running sum, so any other value would
+        // count them as looked up some other number of times, or cancel other queries
+        meta.create_gate("gate should be boolean", |meta| {
+            let q_lookup = meta.query_selector(q_lookup);
+            let gate = gate_expression.unwrap();
+            let one = Expression::Constant(Field::one());
+            vec![q_lookup * gate.clone() * (one - gate)]
+        });
+
+        LogUpConfig {
+            q_lookup,
+            q_value: None,
+            q_first_lookup,
+            q_last_lookup,
+            lookup_table,
+            value_table: Vec::new(),
+            multiplicities,
+            theta,
+            beta,
+            phi,
+        }
+    }
+
+    // Assigns the `table_height` rows of `lookup_table` and the `query_height` rows of
+    // `value_table`, both given column by column, with the multiplicities of the table rows and
+    // the running sum. Only the first `query_height` rows of the value columns are queries, so
+    // unlike `LookupChip::assign` the values are not padded to the height of the table; the
+    // remaining value cells are set to 0. Synthesis fails when a row of `value_table` is not a
+    // row of `lookup_table`, see [`multiplicities`].
+    pub fn assign(
+        &self,
+        layouter: impl Layouter<Field>,
+        table_height: usize,
+        query_height: usize,
+        lookup_table: Value<Vec<Vec<Field>>>,
+        value_table: Value<Vec<Vec<Field>>>,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+        let width = config.width();
+        let q_value = config
+            .q_value
+            .expect("the chip was configured with configure_vector, use assign_vector");
+        let height = table_height.max(query_height);
+        let queries = value_table.as_ref().map(|value_table| {
+            to_rows(value_table, width, query_height)
+                .into_iter()
+                .map(Some)
+                .collect()
+        });
+
+        self.assign_vector(
+            layouter,
+            table_height,
+            query_height,
+            lookup_table,
+            queries,
+            |region| {
+                for offset in 0..query_height {
+                    q_value.enable(region, offset)?;
+                }
+                for (idx, &column) in config.value_table.iter().enumerate() {
+                    for offset in 0..height {
+                        let value = if offset < query_height {
+                            value_table.as_ref().map(|table| table[idx][offset])
+                        } else {
+                            Value::known(Field::zero())
+                        };
+                        region.assign_advice(
+                            || format!("value_table[{}][{}]", idx, offset),
+                            column,
+                            offset,
+                            || value,
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    // Assigns the `table_height` rows of `lookup_table`, given column by column, and the running
+    // sum over `query_height` rows of queries of a chip configured with
+    // [`Self::configure_vector`]. `queries` holds the inputs of each row, `None` where the gate
+    // is 0, and `assign_rows` assigns the cells the gate and the inputs are computed from at
+    // every offset of the region, `0..max(table_height, query_height)`; rows past
+    // `query_height` are not queries, so their gate must be 0. Synthesis fails when an input row
+    // is not a row of `lookup_table`.
+    pub fn assign_vector(
+        &self,
+        layouter: impl Layouter<Field>,
+        table_height: usize,
+        query_height: usize,
+        lookup_table: Value<Vec<Vec<Field>>>,
+        queries: Value<Vec<Option<Vec<Field>>>>,
+        assign_rows: impl FnMut(&mut Region<'_, Field>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+        let width = config.width();
+        assert!(table_height > 0, "lookup table must have at least one row");
+        let height = table_height.max(query_height);
+        // padding table rows repeat the first row, which takes all its lookups
+        let lookup_table = lookup_table.map(|lookup_table| {
+            let mut lookup_rows = to_rows(&lookup_table, width, table_height);
+            lookup_rows.resize(height, lookup_rows[0].clone());
+            lookup_rows
+        });
+        let queries = queries.map(|mut queries| {
+            assert_eq!(
+                queries.len(),
+                query_height,
+                "queries must have {} rows",
+                query_height
+            );
+            queries.resize(height, None);
+            queries
+        });
+        let counts = lookup_table
+            .as_ref()
+            .zip(queries.as_ref())
+            .map(|(lookup_table, queries)| gated_multiplicities(lookup_table, queries));
+        // a query missing from the table leaves no valid witness
+        counts.error_if_known_and(|counts| counts.is_err())?;
+        let counts = counts.map(|counts| counts.unwrap());
+        let lookup_table = lookup_table.map(|table| to_columns(&table, width));
+
+        self.assign_multiplicities(layouter, height, lookup_table, queries, counts, assign_rows)
+    }
+
+    // Assigns `lookup_table`, given column by column, and the multiplicities `counts` of its
+    // rows over `height` rows, with the running sum of the `queries` of each row; the table and
+    // the queries are already padded to `height` rows and `assign_rows` is called like in
+    // [`Self::assign_vector`]. The multiplicities are not checked, so that tests can assign
+    // tampered witnesses.
+    pub fn assign_multiplicities(
+        &self,
+        mut layouter: impl Layouter<Field>,
+        height: usize,
+        lookup_table: Value<Vec<Vec<Field>>>,
+        queries: Value<Vec<Option<Vec<Field>>>>,
+        counts: Value<Vec<u64>>,
+        mut assign_rows: impl FnMut(&mut Region<'_, Field>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+        let theta = layouter.get_challenge(config.theta);
+        let beta = layouter.get_challenge(config.beta);
+        layouter.assign_region(
+            || "lookup value_table in lookup_table",
+            |mut region| {
+                config.q_first_lookup.enable(&mut region, 0)?;
+                config.q_last_lookup.enable(&mut region, height)?;
+                for offset in 0..height {
+                    config.q_lookup.enable(&mut region, offset)?;
+                }
+
+                // First phase
+                for (idx, &column) in config.lookup_table.iter().enumerate() {
+                    for offset in 0..height {
+                        assign_table_cell(
+                            &mut region,
+                            || format!("lookup_table[{}][{}]", idx, offset),
+                            column,
+                            offset,
+                            lookup_table.as_ref().map(|table| table[idx][offset]),
+                        )?;
+                    }
+                }
+                assign_rows(&mut region)?;
+                for offset in 0..height {
+                    region.assign_advice(
+                        || format!("multiplicities[{}]", offset),
+                        config.multiplicities,
+                        offset,
+                        || counts.as_ref().map(|counts| Field::from(counts[offset])),
+                    )?;
+                }
+
+                // Second phase
+
+                // compute phi
+                let phi = lookup_table
+                    .as_ref()
+                    .zip(queries.as_ref())
+                    .zip(counts.as_ref())
+                    .zip(theta.zip(beta))
+                    .map(|(((lookup_table, queries), counts), (theta, beta))| {
+                        let compress_row = |row: &[Field]| {
+                            row.iter()
+                                .fold(Field::zero(), |acc, &element| acc * theta + element)
+                        };
+                        let compress_column = |table: &[Vec<Field>], index: usize| {
+                            table
+                                .iter()
+                                .fold(Field::zero(), |acc, column| acc * theta + column[index])
+                        };
+                        // rows without a query contribute nothing; one stands in for their
+                        // inverse so that the batch inversion sees no zero
+                        let mut input_inverses = queries
+                            .iter()
+                            .map(|query| match query {
+                                Some(inputs) => compress_row(inputs) + beta,
+                                None => Field::one(),
+                            })
+                            .collect::<Vec<_>>();
+                        let mut lookup_inverses = (0..height)
+                            .map(|index| compress_column(lookup_table, index) + beta)
+                            .collect::<Vec<_>>();
+                        input_inverses
+                            .iter_mut()
+                            .chain(lookup_inverses.iter_mut())
+                            .batch_invert();
+                        let terms = (0..height).map(|index| {
+                            let input = match queries[index] {
+                                Some(_) => input_inverses[index],
+                                None => Field::zero(),
+                            };
+                            input - Field::from(counts[index]) * lookup_inverses[index]
+                        });
+                        iter::once(Field::zero())
+                            .chain(terms)
+                            .scan(Field::zero(), |state, cur| {
+                                *state += &cur;
+                                Some(*state)
+                            })
+                            .collect::<Vec<_>>()
+                    });
+                for (offset, value) in phi.transpose_vec(height + 1).into_iter().enumerate() {
+                    region.assign_advice(
+                        || format!("phi[{}]", offset),
+                        config.phi,
+                        offset,
+                        || value,
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+// Returns how many times each row of `lookup_table` is looked up by `value_table`, both given
+// row by row; a row repeated in the lookup table is counted on its first occurrence only
+pub fn multiplicities<Field: FieldExt>(
+    lookup_table: &[Vec<Field>],
+    value_table: &[Vec<Field>],
+) -> Result<Vec<u64>, LookupError> {
+    let queries: Vec<_> = value_table.iter().cloned().map(Some).collect();
+    gated_multiplicities(lookup_table, &queries)
+}
+
+// Multiplicities of the table rows when only the rows of `queries` holding inputs are looked up
+fn gated_multiplicities<Field: FieldExt>(
+    lookup_table: &[Vec<Field>],
+    queries: &[Option<Vec<Field>>],
+) -> Result<Vec<u64>, LookupError> {
+    let mut index_of: HashMap<Vec<u8>, usize> = HashMap::new();
+    for (index, row) in lookup_table.iter().enumerate() {
+        index_of.entry(row_key(row)).or_insert(index);
+    }
+
+    let mut counts = vec![0; lookup_table.len()];
+    for (index, row) in queries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, query)| Some((index, query.as_ref()?)))
+    {
+        let row = *index_of
+            .get(&row_key(row))
+            .ok_or(LookupError::MissingRow { index })?;
+        counts[row] += 1;
+    }
+    Ok(counts)
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::tests::{assert_rejected, test_prover};
+    use halo2_proofs::{circuit::floor_planner::V1, dev::MockProver};
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    const WIDTH: usize = 2;
+    const K: u32 = 6;
+
+    const PHI_END_GATE: &str = "phi should end with 0";
+
+    fn to_fp_rows(table: &[[u64; WIDTH]]) -> Vec<Vec<Fp>> {
+        table
+            .iter()
+            .map(|row| row.iter().map(|&v| Fp::from(v)).collect())
+            .collect()
+    }
+
+    // tables given row by row
+    #[derive(Clone, Default)]
+    struct LogUpCircuit {
+        lookup_table: Vec<[u64; WIDTH]>,
+        value_table: Vec<[u64; WIDTH]>,
+    }
+
+    impl Circuit<Fp> for LogUpCircuit {
+        type Config = LogUpConfig;
+        type FloorPlanner = V1;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let lookup_table = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase).into())
+                .collect();
+            let value_table = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase))
+                .collect();
+            LogUpChip::configure(meta, lookup_table, value_table)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let columns = |rows: &[[u64; WIDTH]]| -> Value<Vec<Vec<Fp>>> {
+                Value::known(
+                    (0..WIDTH)
+                        .map(|j| rows.iter().map(|row| Fp::from(row[j])).collect())
+                        .collect(),
+                )
+            };
+            LogUpChip::construct(config).assign(
+                layouter,
+                self.lookup_table.len(),
+                self.value_table.len(),
+                columns(&self.lookup_table),
+                columns(&self.value_table),
+            )
+        }
+    }
+
+    // Circuit assigning the values and the multiplicities exactly as given, so that witnesses
+    // can be tampered with; both tables have the same height
+    #[derive(Clone)]
+    struct TamperedCircuit {
+        lookup_table: Vec<[u64; WIDTH]>,
+        value_table: Vec<[u64; WIDTH]>,
+        multiplicities: Vec<u64>,
+    }
+
+    impl TamperedCircuit {
+        fn honest(lookup_table: Vec<[u64; WIDTH]>, value_table: Vec<[u64; WIDTH]>) -> Self {
+            let multiplicities =
+                multiplicities(&to_fp_rows(&lookup_table), &to_fp_rows(&value_table)).unwrap();
+            Self {
+                lookup_table,
+                value_table,
+                multiplicities,
+            }
+        }
+    }
+
+    impl Circuit<Fp> for TamperedCircuit {
+        type Config = LogUpConfig;
+        type FloorPlanner = V1;
+        type Params = ();
+
+        // the tables are needed by the key generation of test_prover, which is the only caller
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            LogUpCircuit::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let value_rows = to_fp_rows(&self.value_table);
+            let queries = value_rows.iter().cloned().map(Some).collect();
+            let q_value = config.q_value.unwrap();
+            let value_columns = config.value_table.clone();
+            LogUpChip::construct(config).assign_multiplicities(
+                layouter,
+                self.lookup_table.len(),
+                Value::known(to_columns(&to_fp_rows(&self.lookup_table), WIDTH)),
+                Value::known(queries),
+                Value::known(self.multiplicities.clone()),
+                |region| {
+                    for (offset, row) in value_rows.iter().enumerate() {
+                        q_value.enable(region, offset)?;
+                        for (&column, &value) in value_columns.iter().zip(row.iter()) {
+                            region.assign_advice(
+                                || "value",
+                                column,
+                                offset,
+                                || Value::known(value),
+                            )?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn counts_multiplicities() {
+        let row = |a: u64| vec![Fp::from(a), Fp::from(a + 1)];
+        let lookup_table = vec![row(1), row(3), row(1), row(5)];
+        let counts = multiplicities(&lookup_table, &[row(5), row(1), row(1)]).unwrap();
+        assert_eq!(counts, vec![2, 0, 0, 1]);
+
+        let err = multiplicities(&lookup_table, &[row(1), row(2)]).unwrap_err();
+        assert_eq!(err, LookupError::MissingRow { index: 1 });
+    }
+
+    #[test]
+    fn accepts_values_from_table() {
+        // the value cells past the queries hold 0, which is not in the table
+        let circuit = LogUpCircuit {
+            lookup_table: vec![[1, 2], [3, 4], [5, 6], [7, 8]],
+            value_table: vec![[5, 6], [1, 2], [5, 6]],
+        };
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // a table shorter than the values
+        let circuit = LogUpCircuit {
+            lookup_table: vec![[1, 2], [3, 4]],
+            value_table: vec![[3, 4], [1, 2], [3, 4], [3, 4], [1, 2]],
+        };
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    // MUL rows looked up in a table of products, the other rows ignored
+    #[derive(Clone, Default)]
+    struct MulCircuit {
+        // is_mul, a, b, c
+        rows: Vec<[u64; 4]>,
+    }
+
+    const PRODUCTS: u64 = 4;
+
+    impl Circuit<Fp> for MulCircuit {
+        type Config = (LogUpConfig, [Column<Advice>; 4], [Column<Advice>; 3]);
+        type FloorPlanner = V1;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let cells = [(); 4].map(|_| meta.advice_column_in(FirstPhase));
+            let table = [(); 3].map(|_| meta.advice_column_in(FirstPhase));
+            let config = LogUpChip::configure_vector(meta, |meta| {
+                let [is_mul, a, b, c] =
+                    cells.map(|column| meta.query_advice(column, Rotation::cur()));
+                let inputs = [a, b, c]
+                    .into_iter()
+                    .zip(table)
+                    .map(|(input, column)| (input, column.into()))
+                    .collect();
+                (is_mul, inputs)
+            });
+            (config, cells, table)
+        }
+
+        fn synthesize(
+            &self,
+            (config, cells, _): Self::Config,
+            layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let products: Vec<[u64; 3]> = (0..PRODUCTS)
+                .flat_map(|a| (0..PRODUCTS).map(move |b| [a, b, a * b]))
+                .collect();
+            let lookup_table = (0..3)
+                .map(|j| products.iter().map(|row| Fp::from(row[j])).collect())
+                .collect();
+            // the region is as tall as the table, rows without a query have their gate off
+            let mut rows = self.rows.clone();
+            rows.resize(products.len(), [0; 4]);
+            let queries = rows
+                .iter()
+                .map(|row| (row[0] == 1).then(|| row[1..].iter().map(|&v| Fp::from(v)).collect()))
+                .collect();
+            LogUpChip::construct(config).assign_vector(
+                layouter,
+                products.len(),
+                rows.len(),
+                Value::known(lookup_table),
+                Value::known(queries),
+                |region| {
+                    for (offset, row) in rows.iter().enumerate() {
+                        for (&column, &value) in cells.iter().zip(row.iter()) {
+                            region.assign_advice(
+                                || "cell",
+                                column,
+                                offset,
+                                || Value::known(Fp::from(value)),
+                            )?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn gated_queries_skip_other_rows() {
+        // the ADD row is not a product, but its gate is off
+        let circuit = MulCircuit {
+            rows: vec![[1, 2, 3, 6], [0, 2, 3, 5], [1, 3, 3, 9], [1, 2, 3, 6]],
+        };
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = MulCircuit {
+            rows: vec![[1, 2, 3, 6], [1, 2, 3, 5]],
+        };
+        assert!(MockProver::run(K, &circuit, vec![]).is_err());
+    }
+
+    #[test]
+    fn rejects_non_boolean_gate() {
+        // the second row is a product, but its gate counts it twice
+        let circuit = MulCircuit {
+            rows: vec![[1, 2, 3, 6], [2, 2, 3, 6]],
+        };
+        assert_rejected(K, circuit, "gate should be boolean");
+    }
+
+    #[test]
+    fn witness_generator_reports_missing_value() {
+        let circuit = LogUpCircuit {
+            lookup_table: vec![[1, 2], [3, 4]],
+            value_table: vec![[1, 2], [2, 1]],
+        };
+        assert!(MockProver::run(K, &circuit, vec![]).is_err());
+    }
+
+    const TABLE: [[u64; WIDTH]; 4] = [[1, 2], [3, 4], [5, 6], [7, 8]];
+
+    #[test]
+    fn accepts_honest_multiplicities() {
+        let circuit = TamperedCircuit::honest(TABLE.to_vec(), vec![[5, 6], [1, 2], [5, 6], [5, 6]]);
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+        test_prover::<EqAffine, _>(K, circuit, true);
+    }
+
+    #[test]
+    fn rejects_tampered_multiplicities() {
+        let mut circuit =
+            TamperedCircuit::honest(TABLE.to_vec(), vec![[5, 6], [1, 2], [5, 6], [5, 6]]);
+        // one lookup of [5, 6] is counted for [3, 4]; the running sum follows the multiplicities
+        // but no longer ends with 0
+        circuit.multiplicities[2] -= 1;
+        circuit.multiplicities[1] += 1;
+        assert_rejected(K, circuit, PHI_END_GATE);
+    }
+
+    #[test]
+    fn rejects_input_missing_from_table() {
+        // [2, 1] is not in the table but is counted as the [1, 2] it replaces
+        let mut circuit =
+            TamperedCircuit::honest(TABLE.to_vec(), vec![[5, 6], [1, 2], [5, 6], [5, 6]]);
+        circuit.value_table[1] = [2, 1];
+        assert_rejected(K, circuit, PHI_END_GATE);
+    }
+}
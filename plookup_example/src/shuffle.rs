@@ -0,0 +1,322 @@
+use crate::chip::{compress, max_height, query_columns};
+use ff::BatchInvert;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Value},
+    plonk::*,
+    poly::Rotation,
+};
+use std::{iter, marker::PhantomData};
+
+// Columns, selectors and challenges of the multi-column shuffle argument: the rows of the
+// shuffled columns are a permutation of the rows of the original columns. Rows are compressed
+// into one field element with the challenge theta, and the grand product z accumulates
+// (original + gamma) / (shuffled + gamma).
+#[derive(Clone, Debug)]
+pub struct ShuffleConfig {
+    q_shuffle: Selector,
+    q_first: Selector,
+    q_last: Selector,
+    original: Vec<Column<Advice>>,
+    shuffled: Vec<Column<Advice>>,
+    theta: Challenge,
+    gamma: Challenge,
+    z: Column<Advice>,
+}
+
+impl ShuffleConfig {
+    // Number of columns of each table
+    pub fn width(&self) -> usize {
+        self.original.len()
+    }
+
+    pub fn original_columns(&self) -> &[Column<Advice>] {
+        &self.original
+    }
+
+    pub fn shuffled_columns(&self) -> &[Column<Advice>] {
+        &self.shuffled
+    }
+}
+
+// Chip proving that two multi-column tables hold the same rows with the same multiplicities,
+// in any order
+pub struct ShuffleChip<Field: FieldExt> {
+    config: ShuffleConfig,
+    _marker: PhantomData<Field>,
+}
+
+impl<Field: FieldExt> Chip<Field> for ShuffleChip<Field> {
+    type Config = ShuffleConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<Field: FieldExt> ShuffleChip<Field> {
+    pub fn construct(config: ShuffleConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    // Tallest tables the chip can assign in a circuit of 2^k rows, see `LookupChip::max_height`
+    pub fn max_height(meta: &ConstraintSystem<Field>, k: u32) -> usize {
+        max_height(meta, k)
+    }
+
+    // Configures the shuffle of `original_columns` into `shuffled_columns`, which set the width
+    // of the tables; the grand product column is allocated here
+    pub fn configure(
+        meta: &mut ConstraintSystem<Field>,
+        original_columns: Vec<Column<Advice>>,
+        shuffled_columns: Vec<Column<Advice>>,
+    ) -> ShuffleConfig {
+        let width = original_columns.len();
+        assert!(width > 0, "tables must have at least one column");
+        assert_eq!(
+            shuffled_columns.len(),
+            width,
+            "tables must have the same width"
+        );
+        let [q_shuffle, q_first, q_last] = [(); 3].map(|_| meta.selector());
+        let original = original_columns;
+        let shuffled = shuffled_columns;
+        let [theta, gamma] = [(); 2].map(|_| meta.challenge_usable_after(FirstPhase));
+        let z = meta.advice_column_in(SecondPhase);
+
+        meta.create_gate("z should start with 1", |meta| {
+            let q_first = meta.query_selector(q_first);
+            let z = meta.query_advice(z, Rotation::cur());
+            let one = Expression::Constant(Field::one());
+            vec![q_first * (one - z)]
+        });
+
+        meta.create_gate("z should end with 1", |meta| {
+            let q_last = meta.query_selector(q_last);
+            let z = meta.query_advice(z, Rotation::cur());
+            let one = Expression::Constant(Field::one());
+            vec![q_last * (one - z)]
+        });
+
+        meta.create_gate("z should have valid transition", |meta| {
+            let q_shuffle = meta.query_selector(q_shuffle);
+            let original = query_columns(meta, &original, Rotation::cur());
+            let shuffled = query_columns(meta, &shuffled, Rotation::cur());
+            let [theta, gamma] = [theta, gamma].map(|challenge| meta.query_challenge(challenge));
+            let [z, z_w] =
+                [Rotation::cur(), Rotation::next()].map(|rotation| meta.query_advice(z, rotation));
+
+            let original_merged = compress(&original, &theta);
+            let shuffled_merged = compress(&shuffled, &theta);
+            vec![
+                q_shuffle
+                    * (z_w * (shuffled_merged + gamma.clone()) - z * (original_merged + gamma)),
+            ]
+        });
+
+        ShuffleConfig {
+            q_shuffle,
+            q_first,
+            q_last,
+            original,
+            shuffled,
+            theta,
+            gamma,
+            z,
+        }
+    }
+
+    // Assigns both tables, given column by column and `height` rows high, and the grand product;
+    // the rows of `shuffled` must be a permutation of the rows of `original`. The region takes
+    // `height + 1` rows, at most [`Self::max_height`] plus one.
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<Field>,
+        height: usize,
+        original: Value<Vec<Vec<Field>>>,
+        shuffled: Value<Vec<Vec<Field>>>,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+        assert!(height > 0, "tables must have at least one row");
+        let theta = layouter.get_challenge(config.theta);
+        let gamma = layouter.get_challenge(config.gamma);
+        layouter.assign_region(
+            || "shuffle original into shuffled",
+            |mut region| {
+                config.q_first.enable(&mut region, 0)?;
+                config.q_last.enable(&mut region, height)?;
+                for offset in 0..height {
+                    config.q_shuffle.enable(&mut region, offset)?;
+                }
+
+                // First phase
+                for (name, columns, table) in [
+                    ("original", &config.original, &original),
+                    ("shuffled", &config.shuffled, &shuffled),
+                ] {
+                    for (idx, &column) in columns.iter().enumerate() {
+                        for offset in 0..height {
+                            region.assign_advice(
+                                || format!("{}[{}][{}]", name, idx, offset),
+                                column,
+                                offset,
+                                || table.as_ref().map(|table| table[idx][offset]),
+                            )?;
+                        }
+                    }
+                }
+
+                // Second phase
+
+                // compute z
+                let z = original
+                    .as_ref()
+                    .zip(shuffled.as_ref())
+                    .zip(theta.zip(gamma))
+                    .map(|((original, shuffled), (theta, gamma))| {
+                        let compress_row = |table: &[Vec<Field>], index: usize| {
+                            table
+                                .iter()
+                                .fold(Field::zero(), |acc, column| acc * theta + column[index])
+                        };
+                        let mut product_vec = (0..height)
+                            .map(|index| compress_row(shuffled, index) + gamma)
+                            .collect::<Vec<_>>();
+                        product_vec.iter_mut().batch_invert();
+                        for (index, product) in product_vec.iter_mut().enumerate() {
+                            *product *= compress_row(original, index) + gamma;
+                        }
+                        iter::once(Field::one())
+                            .chain(product_vec)
+                            .scan(Field::one(), |state, cur| {
+                                *state *= &cur;
+                                Some(*state)
+                            })
+                            .collect::<Vec<_>>()
+                    });
+                for (offset, value) in z.transpose_vec(height + 1).into_iter().enumerate() {
+                    region.assign_advice(
+                        || format!("z[{}]", offset),
+                        config.z,
+                        offset,
+                        || value,
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::floor_planner::V1,
+        dev::{MockProver, VerifyFailure},
+    };
+    use halo2curves::pasta::Fp;
+
+    const WIDTH: usize = 2;
+    const K: u32 = 6;
+
+    // tables given column by column
+    #[derive(Clone, Default)]
+    struct ShuffleCircuit {
+        height: usize,
+        original: Value<Vec<Vec<Fp>>>,
+        shuffled: Value<Vec<Vec<Fp>>>,
+    }
+
+    impl ShuffleCircuit {
+        fn new(original: &[[u64; WIDTH]], shuffled: &[[u64; WIDTH]]) -> Self {
+            let columns = |rows: &[[u64; WIDTH]]| -> Value<Vec<Vec<Fp>>> {
+                Value::known(
+                    (0..WIDTH)
+                        .map(|j| rows.iter().map(|row| Fp::from(row[j])).collect())
+                        .collect(),
+                )
+            };
+            Self {
+                height: original.len(),
+                original: columns(original),
+                shuffled: columns(shuffled),
+            }
+        }
+    }
+
+    impl Circuit<Fp> for ShuffleCircuit {
+        type Config = ShuffleConfig;
+        type FloorPlanner = V1;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                height: self.height,
+                ..Self::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let original = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase))
+                .collect();
+            let shuffled = (0..WIDTH)
+                .map(|_| meta.advice_column_in(FirstPhase))
+                .collect();
+            ShuffleChip::configure(meta, original, shuffled)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            ShuffleChip::construct(config).assign(
+                layouter,
+                self.height,
+                self.original.clone(),
+                self.shuffled.clone(),
+            )
+        }
+    }
+
+    #[test]
+    fn accepts_permutation() {
+        let circuit = ShuffleCircuit::new(
+            &[[1, 2], [3, 4], [1, 2], [5, 6]],
+            &[[5, 6], [1, 2], [3, 4], [1, 2]],
+        );
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_other_multiplicities() {
+        // same rows, but [1, 2] appears once more and [3, 4] once less
+        let circuit = ShuffleCircuit::new(
+            &[[1, 2], [3, 4], [1, 2], [5, 6]],
+            &[[5, 6], [1, 2], [1, 2], [1, 2]],
+        );
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        let failures = prover.verify().unwrap_err();
+        assert_eq!(failures.len(), 1);
+        match &failures[0] {
+            VerifyFailure::ConstraintNotSatisfied { constraint, .. } => {
+                assert!(constraint.to_string().contains("('z should end with 1')"))
+            }
+            failure => panic!("unexpected failure {:?}", failure),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::{ProofSystem, ProofSystemError};
+use crate::snark::{program_commitment, DVMCircuit};
+use ark_bls12_377::{Bls12_377, Fr};
+use ark_groth16::{
+    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    PreparedVerifyingKey, Proof, ProvingKey,
+};
+use ark_serialize::CanonicalSerialize;
+use rand_core::OsRng;
+
+/// Groth16 over BLS12-377, proving `DVMCircuit`
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Groth16;
+
+impl Groth16 {
+    /// Public inputs of `DVMCircuit` for `program` returning `result`
+    pub fn public_inputs(program: &[u8], result: i32) -> Vec<Fr> {
+        vec![program_commitment(program, result)]
+    }
+}
+
+impl ProofSystem for Groth16 {
+    type Circuit = DVMCircuit<Fr>;
+    type PublicInputs = Vec<Fr>;
+    type ProvingKey = ProvingKey<Bls12_377>;
+    type VerifyingKey = PreparedVerifyingKey<Bls12_377>;
+    type Proof = Proof<Bls12_377>;
+
+    fn setup(
+        &self,
+        circuit: &Self::Circuit,
+    ) -> Result<(Self::ProvingKey, Self::VerifyingKey), ProofSystemError> {
+        // the toxic waste must stay unpredictable, so it is drawn from the OS
+        let params = generate_random_parameters::<Bls12_377, _, _>(circuit.clone(), &mut OsRng)
+            .map_err(|e| ProofSystemError::Setup(e.to_string()))?;
+        let pvk = prepare_verifying_key(&params.vk);
+        Ok((params, pvk))
+    }
+
+    fn prove(
+        &self,
+        pk: &Self::ProvingKey,
+        circuit: Self::Circuit,
+        _public_inputs: &Self::PublicInputs,
+    ) -> Result<Self::Proof, ProofSystemError> {
+        create_random_proof(circuit, pk, &mut OsRng)
+            .map_err(|e| ProofSystemError::Prover(e.to_string()))
+    }
+
+    fn verify(
+        &self,
+        vk: &Self::VerifyingKey,
+        public_inputs: &Self::PublicInputs,
+        proof: &Self::Proof,
+    ) -> Result<bool, ProofSystemError> {
+        verify_proof(vk, proof, public_inputs)
+            .map_err(|e| ProofSystemError::Verifier(e.to_string()))
+    }
+
+    fn serialize_proof(&self, proof: &Self::Proof) -> Result<Vec<u8>, ProofSystemError> {
+        let mut bytes = Vec::new();
+        proof
+            .serialize(&mut bytes)
+            .map_err(|e| ProofSystemError::Serialization(e.to_string()))?;
+        Ok(bytes)
+    }
+}
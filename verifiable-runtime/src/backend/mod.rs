@@ -0,0 +1,211 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+mod groth16;
+pub use groth16::Groth16;
+
+#[cfg(feature = "stark")]
+mod stark;
+#[cfg(feature = "stark")]
+pub use self::stark::Stark;
+
+/// Error raised by any step of a proof system
+#[derive(Debug)]
+pub enum ProofSystemError {
+    Setup(String),
+    Prover(String),
+    Verifier(String),
+    Serialization(String),
+}
+
+impl fmt::Display for ProofSystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Setup(e) => write!(f, "setup failed: {}", e),
+            Self::Prover(e) => write!(f, "proving failed: {}", e),
+            Self::Verifier(e) => write!(f, "verification failed: {}", e),
+            Self::Serialization(e) => write!(f, "proof serialization failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProofSystemError {}
+
+/// Common interface of the proving backends, so the same statement can be proved with
+/// Groth16, STARK or Halo2 and the results compared.
+pub trait ProofSystem {
+    /// Statement to prove together with its witness
+    type Circuit;
+    type PublicInputs;
+    type ProvingKey;
+    type VerifyingKey;
+    type Proof;
+
+    /// Generates the proving and verifying keys; transparent systems return empty keys
+    fn setup(
+        &self,
+        circuit: &Self::Circuit,
+    ) -> Result<(Self::ProvingKey, Self::VerifyingKey), ProofSystemError>;
+
+    fn prove(
+        &self,
+        pk: &Self::ProvingKey,
+        circuit: Self::Circuit,
+        public_inputs: &Self::PublicInputs,
+    ) -> Result<Self::Proof, ProofSystemError>;
+
+    /// Returns `Ok(false)` when the proof is rejected, errors are reserved for malformed input
+    fn verify(
+        &self,
+        vk: &Self::VerifyingKey,
+        public_inputs: &Self::PublicInputs,
+        proof: &Self::Proof,
+    ) -> Result<bool, ProofSystemError>;
+
+    fn serialize_proof(&self, proof: &Self::Proof) -> Result<Vec<u8>, ProofSystemError>;
+}
+
+/// Proving backends, labelling the reports of [run].
+///
+/// Only Groth16 and STARK have a DVM circuit, so [prove_dvm] compares those two. Halo2 with the
+/// IPA commitment scheme is implemented by `Halo2Ipa` of the `lookup` crate, which depends on
+/// this one, and proves the lookup circuits of that crate.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Backend {
+    Groth16,
+    #[cfg(feature = "stark")]
+    Stark,
+    Halo2,
+}
+
+/// Outcome of proving a statement with one backend
+#[derive(Debug)]
+pub struct ProofReport {
+    pub backend: Backend,
+    pub proof_size: usize,
+    pub setup_time: Duration,
+    pub prove_time: Duration,
+    pub verify_time: Duration,
+    pub verified: bool,
+}
+
+/// Runs setup, prove and verify with the given proof system and measures each step
+pub fn run<P: ProofSystem>(
+    backend: Backend,
+    system: &P,
+    circuit: P::Circuit,
+    public_inputs: &P::PublicInputs,
+) -> Result<ProofReport, ProofSystemError> {
+    let start = Instant::now();
+    let (pk, vk) = system.setup(&circuit)?;
+    let setup_time = start.elapsed();
+
+    let start = Instant::now();
+    let proof = system.prove(&pk, circuit, public_inputs)?;
+    let prove_time = start.elapsed();
+
+    let start = Instant::now();
+    let verified = system.verify(&vk, public_inputs, &proof)?;
+    let verify_time = start.elapsed();
+
+    Ok(ProofReport {
+        backend,
+        proof_size: system.serialize_proof(&proof)?.len(),
+        setup_time,
+        prove_time,
+        verify_time,
+        verified,
+    })
+}
+
+/// Proves that `program` returns `result` with the selected backend; Halo2 is rejected, as it
+/// has no DVM circuit
+pub fn prove_dvm(
+    backend: Backend,
+    program: Vec<u8>,
+    result: i32,
+) -> Result<ProofReport, ProofSystemError> {
+    match backend {
+        Backend::Groth16 => {
+            let public_inputs = Groth16::public_inputs(&program, result);
+            let circuit = crate::snark::DVMCircuit::new(program, result);
+            run(backend, &Groth16, circuit, &public_inputs)
+        }
+        #[cfg(feature = "stark")]
+        Backend::Stark => {
+            let public_inputs = Stark::public_inputs(program.clone(), result);
+            run(backend, &Stark::default(), program, &public_inputs)
+        }
+        Backend::Halo2 => Err(ProofSystemError::Setup(
+            "Halo2 has no DVM circuit, DVM programs are proved with Groth16 or STARK".to_string(),
+        )),
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PUSH 6, PUSH 7, MUL, RET
+    const PROGRAM: [u8; 12] = [
+        0x05, 0x00, 0x00, 0x00, 0x06, 0x05, 0x00, 0x00, 0x00, 0x07, 0x03, 0x07,
+    ];
+
+    #[test]
+    fn proves_dvm_with_groth16() {
+        let report = prove_dvm(Backend::Groth16, PROGRAM.to_vec(), 42).unwrap();
+        assert_eq!(report.backend, Backend::Groth16);
+        assert!(report.verified);
+        assert!(report.proof_size > 0);
+    }
+
+    #[test]
+    fn groth16_rejects_wrong_public_input() {
+        let circuit = crate::snark::DVMCircuit::new(PROGRAM.to_vec(), 42);
+        let public_inputs = Groth16::public_inputs(&PROGRAM, 42);
+        let (pk, vk) = Groth16.setup(&circuit).unwrap();
+        let proof = Groth16.prove(&pk, circuit, &public_inputs).unwrap();
+        assert!(Groth16.verify(&vk, &public_inputs, &proof).unwrap());
+
+        let wrong = Groth16::public_inputs(&PROGRAM, 43);
+        assert!(!Groth16.verify(&vk, &wrong, &proof).unwrap());
+    }
+
+    #[test]
+    fn halo2_has_no_dvm_circuit() {
+        assert!(matches!(
+            prove_dvm(Backend::Halo2, PROGRAM.to_vec(), 42),
+            Err(ProofSystemError::Setup(_))
+        ));
+    }
+
+    #[cfg(feature = "stark")]
+    #[test]
+    fn proves_dvm_with_stark() {
+        let report = prove_dvm(Backend::Stark, PROGRAM.to_vec(), 42).unwrap();
+        assert_eq!(report.backend, Backend::Stark);
+        assert!(report.verified);
+        assert!(report.proof_size > 0);
+    }
+
+    #[cfg(feature = "stark")]
+    #[test]
+    fn stark_rejects_wrong_public_input() {
+        let stark = Stark::default();
+        let public_inputs = Stark::public_inputs(PROGRAM.to_vec(), 42);
+        let proof = stark.prove(&(), PROGRAM.to_vec(), &public_inputs).unwrap();
+        assert!(stark.verify(&(), &public_inputs, &proof).unwrap());
+
+        // a wrong result fails the final assertion, while another program does not even match
+        // the trace width of the proof; both are rejections rather than errors
+        let wrong_result = Stark::public_inputs(PROGRAM.to_vec(), 43);
+        assert!(!stark.verify(&(), &wrong_result, &proof).unwrap());
+        let mut other = PROGRAM.to_vec();
+        other.splice(..0, [0x05, 0x00, 0x00, 0x00, 0x01]);
+        let other_program = Stark::public_inputs(other, 42);
+        assert!(!stark.verify(&(), &other_program, &proof).unwrap());
+    }
+}
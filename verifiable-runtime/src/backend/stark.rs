@@ -0,0 +1,83 @@
+use super::{ProofSystem, ProofSystemError};
+use crate::opcode::decode;
+use crate::stark::{
+    build_trace, default_proof_options, stack_depth, to_element, DVMAir, DVMProver, PublicInputs,
+    STACK_COL,
+};
+use winterfell::{
+    crypto::hashers::Blake3_256, math::fields::f128::BaseElement, ProofOptions, Prover, StarkProof,
+};
+
+/// Winterfell STARK proving `DVMAir`; there is no trusted setup, so keys are empty
+#[derive(Clone)]
+pub struct Stark {
+    options: ProofOptions,
+}
+
+impl Stark {
+    pub fn new(options: ProofOptions) -> Self {
+        Self { options }
+    }
+
+    /// Public inputs of `DVMAir` for `program` returning `result`
+    pub fn public_inputs(program: Vec<u8>, result: i32) -> PublicInputs {
+        PublicInputs {
+            program,
+            result: to_element(result),
+        }
+    }
+}
+
+impl Default for Stark {
+    fn default() -> Self {
+        Self::new(default_proof_options())
+    }
+}
+
+impl ProofSystem for Stark {
+    /// The program itself; the trace is generated while proving
+    type Circuit = Vec<u8>;
+    type PublicInputs = PublicInputs;
+    type ProvingKey = ();
+    type VerifyingKey = ();
+    type Proof = StarkProof;
+
+    fn setup(&self, _circuit: &Self::Circuit) -> Result<((), ()), ProofSystemError> {
+        Ok(((), ()))
+    }
+
+    fn prove(
+        &self,
+        _pk: &(),
+        program: Self::Circuit,
+        _public_inputs: &Self::PublicInputs,
+    ) -> Result<Self::Proof, ProofSystemError> {
+        let trace = build_trace(&program);
+        DVMProver::new(self.options.clone(), program)
+            .prove(trace)
+            .map_err(|e| ProofSystemError::Prover(e.to_string()))
+    }
+
+    fn verify(
+        &self,
+        _vk: &(),
+        public_inputs: &Self::PublicInputs,
+        proof: &Self::Proof,
+    ) -> Result<bool, ProofSystemError> {
+        // `DVMAir` asserts that the trace fits the program, so a proof of another program must
+        // be rejected before the verifier builds the AIR
+        let width = STACK_COL + stack_depth(&decode(&public_inputs.program));
+        if proof.get_trace_info().width() != width {
+            return Ok(false);
+        }
+        Ok(winterfell::verify::<DVMAir, Blake3_256<BaseElement>>(
+            proof.clone(),
+            public_inputs.clone(),
+        )
+        .is_ok())
+    }
+
+    fn serialize_proof(&self, proof: &Self::Proof) -> Result<Vec<u8>, ProofSystemError> {
+        Ok(proof.to_bytes())
+    }
+}
@@ -1,11 +1,21 @@
-use ark_r1cs_std::fields::fp;
 use std::vec;
+use vrt::backend::{prove_dvm, Backend};
 use vrt::dvm::DVM;
-use vrt::snark::verify_dvm_circuit_groth16;
-
-use ark_ff::{BigInteger64, Field, Fp64, Fp64Parameters, PrimeField};
+use vrt::snark::{cost::cost_report, verify_dvm_circuit_groth16};
 
 fn main() {
+    // `cost <program in hex>` reports the size of the Groth16 circuit of a program
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command, program] = args.as_slice() {
+        if command == "cost" {
+            match parse_hex(program) {
+                Some(program) => print_cost(program),
+                None => eprintln!("program must be an even number of hex digits"),
+            }
+            return;
+        }
+    }
+
     // 45022 - ((86 + 119)*34)/2
     // Example program in DVM opcode!
     // PUSH 0x00000056     0x05, 0x00, 0x00, 0x00, 0x56
@@ -26,7 +36,47 @@ fn main() {
         0x23, 0x02, 0x07,
     ];
     let mut my_dummy_vm = DVM::new();
-    let result = my_dummy_vm.process(program);
+    let result = my_dummy_vm.process(program.clone());
     println!("Result: {}", result);
     verify_dvm_circuit_groth16(result);
+    #[cfg(feature = "stark")]
+    vrt::stark::verify_dvm_stark(program.clone(), result);
+
+    // Compare the backends with a DVM circuit on the same program; Halo2 only proves the
+    // lookup circuits of the lookup crate
+    let backends = [
+        Backend::Groth16,
+        #[cfg(feature = "stark")]
+        Backend::Stark,
+    ];
+    for backend in backends {
+        let report = prove_dvm(backend, program.clone(), result).unwrap();
+        println!(
+            "{:?}: verified {}, proof {} bytes, setup {:?}, prove {:?}, verify {:?}",
+            report.backend,
+            report.verified,
+            report.proof_size,
+            report.setup_time,
+            report.prove_time,
+            report.verify_time
+        );
+    }
+}
+
+fn print_cost(program: Vec<u8>) {
+    match cost_report(&program) {
+        Ok(report) => println!("{}", report),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim_start_matches("0x");
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
 }
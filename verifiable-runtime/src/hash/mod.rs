@@ -0,0 +1,62 @@
+//! Algebraic hash functions, computed natively and inside R1CS circuits.
+use ark_ff::PrimeField;
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSystem, ConstraintSystemRef, SynthesisError};
+use sha3::{
+    digest::{ExtendableOutput, Update},
+    Shake256, Shake256Reader,
+};
+
+pub mod poseidon;
+pub mod rescue;
+
+/// Duplex sponge over elements of `F`.
+///
+/// The first squeeze after absorbing pads the input, so inputs of different lengths never give
+/// the same output. Absorbing after squeezing starts a new input.
+pub trait AlgebraicSponge<F>: Sized {
+    type Parameters;
+
+    /// Returns a sponge for `domain`; sponges of different domains give unrelated outputs.
+    fn with_domain(params: &Self::Parameters, domain: F) -> Self;
+
+    fn absorb(&mut self, elements: &[F]);
+
+    fn squeeze(&mut self, num_elements: usize) -> Vec<F>;
+}
+
+/// In-circuit counterpart of [AlgebraicSponge]; given the same parameters, domain and input it
+/// squeezes the same elements as the native sponge.
+pub trait AlgebraicSpongeGadget<F: PrimeField>: Sized {
+    type Parameters;
+
+    fn with_domain(cs: ConstraintSystemRef<F>, params: &Self::Parameters, domain: F) -> Self;
+
+    fn absorb(&mut self, elements: &[FpVar<F>]) -> Result<(), SynthesisError>;
+
+    fn squeeze(&mut self, num_elements: usize) -> Result<Vec<FpVar<F>>, SynthesisError>;
+}
+
+/// Returns the number of constraints `S` spends absorbing `num_elements` witnesses and
+/// squeezing one element, to compare sponges for a given input size.
+pub fn sponge_cost<F: PrimeField, S: AlgebraicSpongeGadget<F>>(
+    params: &S::Parameters,
+    num_elements: usize,
+) -> Result<usize, SynthesisError> {
+    let cs = ConstraintSystem::<F>::new_ref();
+    let elements = (0..num_elements)
+        .map(|i| FpVar::new_witness(cs.clone(), || Ok(F::from(i as u64))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut sponge = S::with_domain(cs.clone(), params, F::zero());
+    sponge.absorb(&elements)?;
+    sponge.squeeze(1)?;
+    Ok(cs.num_constraints())
+}
+
+/// Returns SHAKE256 seeded with `seed`, the source of round constants of derived instances.
+fn shake256(seed: &str) -> Shake256Reader {
+    let mut shake = Shake256::default();
+    shake.update(seed.as_bytes());
+    shake.finalize_xof()
+}
@@ -0,0 +1,116 @@
+//! Poseidon sponge from `ark-sponge`, wrapped to follow the same padding and domain separation
+//! rules as the Rescue sponges.
+//!
+//! `ark-sponge` only implements a width-3 state (rate 2, capacity 1) and leaves the parameters
+//! to the caller. The round numbers are those of the arkworks default configuration for
+//! `alpha = 17` at 128-bit security; round constants are squeezed from SHAKE256 like the Rescue
+//! constants, and the MDS matrix is a Cauchy matrix. Digests therefore differ from other
+//! Poseidon implementations, which use the Grain LFSR for constants.
+#![deny(unsafe_code)]
+
+use super::{
+    rescue::{gcd, RescueField},
+    shake256, AlgebraicSponge,
+};
+use ark_ff::PrimeField;
+use ark_sponge::{
+    poseidon::{PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use sha3::digest::XofReader;
+
+pub mod constraints;
+
+/// State width supported by `ark-sponge`
+pub const STATE_WIDTH: usize = 3;
+
+/// S-box exponent; it must be coprime to `p - 1`, which holds for the BLS12-377 and BLS12-381
+/// scalar fields
+pub const ALPHA: u64 = 17;
+
+pub const FULL_ROUNDS: u32 = 8;
+pub const PARTIAL_ROUNDS: u32 = 31;
+
+// PARAMETERS
+// ================================================================================================
+
+/// Returns the Poseidon parameters for `F`.
+pub fn poseidon_parameters<F: PrimeField + RescueField>() -> PoseidonParameters<F> {
+    // x^alpha is a permutation exactly when alpha is coprime to p - 1
+    let rem = (F::modulus() - 1u32) % ALPHA;
+    assert!(
+        gcd(ALPHA, rem.to_u64_digits().first().copied().unwrap_or(0)) == 1,
+        "x^{} is not a permutation of the field",
+        ALPHA
+    );
+
+    let seed = format!(
+        "Poseidon({},{},{},{},{})",
+        F::modulus(),
+        STATE_WIDTH,
+        FULL_ROUNDS,
+        PARTIAL_ROUNDS,
+        ALPHA
+    );
+    let mut reader = shake256(&seed);
+    let mut chunk = vec![0u8; (F::modulus().bits() as usize).div_ceil(8) + 1];
+    let ark = (0..FULL_ROUNDS + PARTIAL_ROUNDS)
+        .map(|_| {
+            (0..STATE_WIDTH)
+                .map(|_| {
+                    reader.read(&mut chunk);
+                    <F as PrimeField>::from_le_bytes_mod_order(&chunk)
+                })
+                .collect()
+        })
+        .collect();
+
+    // Cauchy matrix 1 / (x_i + y_j) with x_i = i and y_j = width + j; all sums are distinct
+    // and non-zero, so every square submatrix is invertible
+    let mds = (0..STATE_WIDTH)
+        .map(|i| {
+            (0..STATE_WIDTH)
+                .map(|j| F::from((i + STATE_WIDTH + j) as u64).inverse().unwrap())
+                .collect()
+        })
+        .collect();
+
+    PoseidonParameters::new(FULL_ROUNDS, PARTIAL_ROUNDS, ALPHA, mds, ark)
+}
+
+// SPONGE
+// ================================================================================================
+
+/// Poseidon sponge; the domain is absorbed first and a ONE is absorbed before the first
+/// squeeze, so inputs of different domains or lengths give unrelated outputs.
+#[derive(Clone)]
+pub struct Poseidon<F: PrimeField> {
+    sponge: PoseidonSponge<F>,
+    squeezing: bool,
+}
+
+impl<F: PrimeField + Absorb> AlgebraicSponge<F> for Poseidon<F> {
+    type Parameters = PoseidonParameters<F>;
+
+    fn with_domain(params: &PoseidonParameters<F>, domain: F) -> Self {
+        let mut sponge = PoseidonSponge::new(params);
+        sponge.absorb(&domain);
+        Poseidon {
+            sponge,
+            squeezing: false,
+        }
+    }
+
+    fn absorb(&mut self, elements: &[F]) {
+        self.squeezing = false;
+        self.sponge.absorb(&elements);
+    }
+
+    fn squeeze(&mut self, num_elements: usize) -> Vec<F> {
+        if !self.squeezing {
+            self.sponge.absorb(&F::one());
+            self.squeezing = true;
+        }
+        self.sponge.squeeze_native_field_elements(num_elements)
+    }
+}
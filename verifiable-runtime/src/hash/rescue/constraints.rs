@@ -0,0 +1,255 @@
+//! R1CS gadget computing the same Rescue sponge as [Rescue] inside an arkworks circuit.
+#[cfg(doc)]
+use super::Rescue;
+use super::{bytes_per_element, RescueField, RescueParameters};
+use crate::hash::AlgebraicSpongeGadget;
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::uint8::UInt8, boolean::Boolean, eq::EqGadget, fields::fp::FpVar,
+    prelude::FieldVar, R1CSVar, ToBitsGadget,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+
+/// In-circuit counterpart of [Rescue]; digests agree with the native sponge for the same
+/// parameters, domain and data.
+#[derive(Clone)]
+pub struct RescueGadget<F: PrimeField + RescueField> {
+    params: RescueParameters<F>,
+    state: Vec<FpVar<F>>,
+    idx: usize,
+    squeezing: bool,
+}
+
+impl<F: PrimeField + RescueField> RescueGadget<F> {
+    /// Returns a new hasher for the default domain.
+    pub fn new(params: &RescueParameters<F>) -> Self {
+        Self::with_domain(params, F::zero())
+    }
+
+    /// Returns a new hasher with `domain` written into the first capacity element.
+    pub fn with_domain(params: &RescueParameters<F>, domain: F) -> Self {
+        let mut state = vec![FpVar::zero(); params.width];
+        state[params.rate] = FpVar::constant(domain);
+        RescueGadget {
+            params: params.clone(),
+            state,
+            idx: 0,
+            squeezing: false,
+        }
+    }
+
+    /// Absorbs data into the hasher state.
+    pub fn update(&mut self, data: &[FpVar<F>]) -> Result<(), SynthesisError> {
+        if self.squeezing {
+            self.squeezing = false;
+            self.idx = 0;
+        }
+        for element in data {
+            self.state[self.idx] += element;
+            self.idx += 1;
+            if self.idx == self.params.rate {
+                apply_permutation(&self.params, &mut self.state)?;
+                self.idx = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next `num_elements` elements of the rate. The first squeeze after absorbing
+    /// applies 10* padding.
+    pub fn squeeze(&mut self, num_elements: usize) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        if !self.squeezing {
+            self.state[self.idx] += F::one();
+            apply_permutation(&self.params, &mut self.state)?;
+            self.squeezing = true;
+            self.idx = 0;
+        }
+        let mut result = Vec::with_capacity(num_elements);
+        for _ in 0..num_elements {
+            if self.idx == self.params.rate {
+                apply_permutation(&self.params, &mut self.state)?;
+                self.idx = 0;
+            }
+            result.push(self.state[self.idx].clone());
+            self.idx += 1;
+        }
+        Ok(result)
+    }
+
+    /// Returns hash of the data absorbed into the hasher, after 10* padding.
+    pub fn finalize(mut self) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        let capacity = self.params.capacity();
+        self.squeeze(capacity)
+    }
+
+    /// Returns hash of the provided data.
+    pub fn digest(
+        params: &RescueParameters<F>,
+        data: &[FpVar<F>],
+    ) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        let mut hasher = Self::new(params);
+        hasher.update(data)?;
+        hasher.finalize()
+    }
+}
+
+impl<F: PrimeField + RescueField> AlgebraicSpongeGadget<F> for RescueGadget<F> {
+    type Parameters = RescueParameters<F>;
+
+    fn with_domain(_cs: ConstraintSystemRef<F>, params: &RescueParameters<F>, domain: F) -> Self {
+        RescueGadget::with_domain(params, domain)
+    }
+
+    fn absorb(&mut self, elements: &[FpVar<F>]) -> Result<(), SynthesisError> {
+        self.update(elements)
+    }
+
+    fn squeeze(&mut self, num_elements: usize) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        RescueGadget::squeeze(self, num_elements)
+    }
+}
+
+/// In-circuit counterpart of [super::bytes_to_elements].
+pub fn bytes_to_elements<F: PrimeField + RescueField>(
+    bytes: &[UInt8<F>],
+) -> Result<Vec<FpVar<F>>, SynthesisError> {
+    let mut padded = bytes.to_vec();
+    padded.push(UInt8::constant(1));
+    padded
+        .chunks(bytes_per_element::<F>())
+        .map(|chunk| Boolean::le_bits_to_fp_var(&chunk.to_bits_le()?))
+        .collect()
+}
+
+/// Enforces Rescue-XLIX permutation of the provided state.
+///
+/// The S-box costs one constraint per multiplication of `x^alpha`; the inverse S-box is not
+/// computed in the circuit but witnessed and checked with the forward S-box.
+pub fn apply_permutation<F: PrimeField + RescueField>(
+    params: &RescueParameters<F>,
+    state: &mut [FpVar<F>],
+) -> Result<(), SynthesisError> {
+    for ark in params.ark.iter() {
+        // apply first half of Rescue round
+        for x in state.iter_mut() {
+            *x = x.pow_by_constant([params.alpha])?;
+        }
+        apply_mds(params, state);
+        for (x, &c) in state.iter_mut().zip(&ark[..params.width]) {
+            *x += c;
+        }
+
+        // apply second half of Rescue round
+        for x in state.iter_mut() {
+            *x = inv_sbox(params, x)?;
+        }
+        apply_mds(params, state);
+        for (x, &c) in state.iter_mut().zip(&ark[params.width..]) {
+            *x += c;
+        }
+    }
+    Ok(())
+}
+
+fn inv_sbox<F: PrimeField + RescueField>(
+    params: &RescueParameters<F>,
+    x: &FpVar<F>,
+) -> Result<FpVar<F>, SynthesisError> {
+    if let FpVar::Constant(c) = x {
+        return Ok(FpVar::constant(c.pow(&params.inv_alpha)));
+    }
+    let y = FpVar::new_witness(x.cs(), || Ok(x.value()?.pow(&params.inv_alpha)))?;
+    y.pow_by_constant([params.alpha])?.enforce_equal(x)?;
+    Ok(y)
+}
+
+fn apply_mds<F: PrimeField + RescueField>(params: &RescueParameters<F>, state: &mut [FpVar<F>]) {
+    let result: Vec<FpVar<F>> = params
+        .mds
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(state.iter())
+                .map(|(&m, x)| x * m)
+                .fold(FpVar::zero(), |acc, term| acc + term)
+        })
+        .collect();
+    state.clone_from_slice(&result);
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::rescue::{self, Rescue};
+    use ark_bls12_377::Fr;
+    use ark_ff::Field;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn params() -> RescueParameters<Fr> {
+        RescueParameters::new(3, 1, 128)
+    }
+
+    #[test]
+    fn parameters_follow_spec() {
+        let params = params();
+        assert_eq!(params.alpha, 11);
+        assert_eq!(params.rounds, 11);
+        assert_eq!(params.ark.len(), params.rounds);
+
+        // x -> x^alpha -> x^(alpha * inv_alpha) is the identity
+        let x = Fr::from(123456789u64);
+        assert_eq!(x.pow([params.alpha]).pow(&params.inv_alpha), x);
+    }
+
+    #[test]
+    fn digest_matches_native() {
+        let params = params();
+        for len in 0..7u64 {
+            let data: Vec<Fr> = (1..=len).map(Fr::from).collect();
+            let expected = Rescue::digest(&params, &data);
+
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let vars = Vec::new_witness(cs.clone(), || Ok(data.clone())).unwrap();
+            let digest = RescueGadget::digest(&params, &vars).unwrap();
+
+            assert_eq!(digest.value().unwrap(), expected);
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn bytes_match_native() {
+        let params = params();
+        let bytes: Vec<u8> = (0..70).collect();
+        let mut native = Rescue::with_domain(&params, Fr::from(7u64));
+        native.update(&rescue::bytes_to_elements(&bytes));
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let vars = UInt8::new_witness_vec(cs.clone(), &bytes).unwrap();
+        let mut hasher = RescueGadget::with_domain(&params, Fr::from(7u64));
+        hasher.update(&bytes_to_elements(&vars).unwrap()).unwrap();
+
+        assert_eq!(
+            hasher.finalize().unwrap().value().unwrap(),
+            native.finalize()
+        );
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn wrong_digest_is_unsatisfied() {
+        let params = params();
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let x = FpVar::new_witness(cs.clone(), || Ok(Fr::from(1u64))).unwrap();
+        let expected = Rescue::digest(&params, &[Fr::from(2u64)]);
+        let digest = FpVar::new_input(cs.clone(), || Ok(expected[0])).unwrap();
+
+        RescueGadget::digest(&params, &[x]).unwrap()[0]
+            .enforce_equal(&digest)
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
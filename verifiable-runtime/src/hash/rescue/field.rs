@@ -0,0 +1,149 @@
+//! Fields Rescue parameters can be derived for.
+//!
+//! arkworks and winterfell fields share no arithmetic trait, and a blanket implementation over
+//! `ark_ff::PrimeField` would conflict with the one for the STARK field, so each supported field
+//! is listed explicitly.
+use core::fmt::Debug;
+use core::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+use num_bigint::BigUint;
+
+/// Prime field arithmetic needed to derive and evaluate a Rescue permutation
+pub trait RescueField:
+    Copy
+    + PartialEq
+    + Debug
+    + Add<Output = Self>
+    + AddAssign
+    + Sub<Output = Self>
+    + SubAssign
+    + Mul<Output = Self>
+    + MulAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// Characteristic of the field
+    fn modulus() -> BigUint;
+
+    /// Distinct prime factors of `p - 1`, to recognize generators of the multiplicative group
+    fn order_factors() -> &'static [u64];
+
+    /// Reduces `value` modulo the characteristic.
+    fn from_biguint(value: &BigUint) -> Self;
+
+    /// Returns the canonical integer representative of `self`.
+    fn to_biguint(self) -> BigUint;
+
+    /// Returns the inverse of a non-zero element, and zero for zero.
+    fn invert(self) -> Self;
+
+    /// Reduces the little-endian integer `bytes` modulo the characteristic.
+    fn from_le_bytes_mod_order(bytes: &[u8]) -> Self {
+        Self::from_biguint(&BigUint::from_bytes_le(bytes))
+    }
+
+    /// Raises `self` to the power of the little-endian `exp` limbs.
+    fn power(self, exp: &[u64]) -> Self {
+        let mut result = Self::ONE;
+        for limb in exp.iter().rev() {
+            for i in (0..64).rev() {
+                result *= result;
+                if (limb >> i) & 1 == 1 {
+                    result *= self;
+                }
+            }
+        }
+        result
+    }
+}
+
+// ARKWORKS FIELDS
+// ================================================================================================
+
+macro_rules! impl_rescue_field {
+    ($($field:ident: $factors:expr),*) => {
+        $(
+            impl RescueField for $field {
+                const ZERO: Self = ark_ff::field_new!($field, "0");
+                const ONE: Self = ark_ff::field_new!($field, "1");
+
+                fn modulus() -> BigUint {
+                    let limbs = <$field as ark_ff::Field>::characteristic();
+                    BigUint::from_bytes_le(
+                        &limbs.iter().flat_map(|limb| limb.to_le_bytes()).collect::<Vec<_>>(),
+                    )
+                }
+
+                fn order_factors() -> &'static [u64] {
+                    &$factors
+                }
+
+                fn from_biguint(value: &BigUint) -> Self {
+                    <$field as ark_ff::PrimeField>::from_le_bytes_mod_order(&value.to_bytes_le())
+                }
+
+                fn to_biguint(self) -> BigUint {
+                    ark_ff::PrimeField::into_repr(&self).into()
+                }
+
+                fn invert(self) -> Self {
+                    ark_ff::Field::inverse(&self).unwrap_or(Self::ZERO)
+                }
+
+                fn power(self, exp: &[u64]) -> Self {
+                    ark_ff::Field::pow(&self, exp)
+                }
+            }
+        )*
+    };
+}
+
+use ark_bls12_377::Fr as Bls12_377Fr;
+use ark_bls12_381::Fr as Bls12_381Fr;
+
+impl_rescue_field!(
+    Bls12_377Fr: [
+        2,
+        3,
+        5,
+        7,
+        13,
+        499,
+        958612291309063373,
+        9586122913090633729
+    ],
+    Bls12_381Fr: [
+        2, 3, 11, 19, 10177, 125527, 859267, 906349, 2508409, 2529403, 52437899, 254760293
+    ]
+);
+
+// STARK FIELD
+// ================================================================================================
+
+#[cfg(feature = "stark")]
+impl RescueField for winterfell::math::fields::f128::BaseElement {
+    const ZERO: Self = <Self as winterfell::math::FieldElement>::ZERO;
+    const ONE: Self = <Self as winterfell::math::FieldElement>::ONE;
+
+    fn modulus() -> BigUint {
+        BigUint::from(<Self as winterfell::math::StarkField>::MODULUS)
+    }
+
+    fn order_factors() -> &'static [u64] {
+        &[2, 29, 181, 286619, 11394379, 18053749339]
+    }
+
+    fn from_biguint(value: &BigUint) -> Self {
+        let reduced = (value % Self::modulus()).to_u64_digits();
+        let limb = |i: usize| reduced.get(i).copied().unwrap_or(0) as u128;
+        Self::new(limb(0) | (limb(1) << 64))
+    }
+
+    fn to_biguint(self) -> BigUint {
+        BigUint::from(winterfell::math::StarkField::as_int(&self))
+    }
+
+    fn invert(self) -> Self {
+        winterfell::math::FieldElement::inv(self)
+    }
+}
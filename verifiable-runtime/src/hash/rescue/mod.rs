@@ -0,0 +1,407 @@
+//! Rescue-Prime over an arbitrary prime field, following <https://eprint.iacr.org/2020/1143.pdf>.
+//!
+//! This is the canonical Rescue implementation. The parameters are derived for any
+//! [RescueField], so the same hash code targets the STARK field as well as the scalar fields of
+//! pairing-friendly curves, where it can also be computed inside an R1CS circuit.
+//! `utils::rescue::Rescue128` is a thin wrapper running this sponge with the hardcoded instance
+//! over the 128-bit STARK field that the AIRs are built on.
+#![deny(unsafe_code)]
+
+use super::{shake256, AlgebraicSponge};
+use num_bigint::BigUint;
+use sha3::digest::XofReader;
+
+pub mod constraints;
+mod field;
+pub use field::RescueField;
+
+// PARAMETERS
+// ================================================================================================
+
+/// Parameters of a Rescue-XLIX permutation over `F`
+#[derive(Clone, Debug)]
+pub struct RescueParameters<F: RescueField> {
+    /// Number of field elements in the state
+    pub width: usize,
+    /// Number of state elements absorbing input; the rest is capacity
+    pub rate: usize,
+    pub rounds: usize,
+    /// Exponent of the S-box; derived instances use the smallest `alpha` coprime to `p - 1`
+    pub alpha: u64,
+    /// Exponent of the inverse S-box, `alpha^-1 mod p - 1`
+    pub inv_alpha: Vec<u64>,
+    /// `width x width` MDS matrix, row by row
+    pub mds: Vec<Vec<F>>,
+    /// Round constants; each round uses `2 * width` of them
+    pub ark: Vec<Vec<F>>,
+}
+
+impl<F: RescueField> RescueParameters<F> {
+    /// Derives parameters for a state of `width` elements, `capacity` of which are not
+    /// absorbing input, targeting `security_level` bits.
+    ///
+    /// Round count and round constants are computed with algorithms 7 and 5 of the Rescue-Prime
+    /// specification. The MDS matrix is built from a Vandermonde matrix over the smallest
+    /// primitive element, as in algorithm 6.
+    pub fn new(width: usize, capacity: usize, security_level: usize) -> Self {
+        assert!(
+            capacity > 0 && capacity < width,
+            "capacity must leave room for the rate"
+        );
+
+        let modulus = F::modulus();
+        let (alpha, inv_alpha) = get_alphas(&modulus);
+        let rounds = get_number_of_rounds(width, capacity, security_level, alpha);
+
+        RescueParameters {
+            width,
+            rate: width - capacity,
+            rounds,
+            alpha,
+            inv_alpha: inv_alpha.to_u64_digits(),
+            mds: get_mds_matrix(width),
+            ark: get_round_constants(&modulus, width, capacity, security_level, rounds),
+        }
+    }
+
+    /// Number of state elements not absorbing input; digests have as many elements.
+    pub fn capacity(&self) -> usize {
+        self.width - self.rate
+    }
+}
+
+/// Smallest `alpha` for which `x -> x^alpha` is a permutation, and its inverse exponent.
+fn get_alphas(modulus: &BigUint) -> (u64, BigUint) {
+    let order = modulus - 1u32;
+    let alpha = (3u64..)
+        .find(|&alpha| {
+            let rem = (&order % alpha)
+                .to_u64_digits()
+                .first()
+                .copied()
+                .unwrap_or(0);
+            gcd(alpha, rem) == 1
+        })
+        .unwrap();
+    let inv_alpha = BigUint::from(alpha).modinv(&order).unwrap();
+    (alpha, inv_alpha)
+}
+
+pub(crate) fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Algorithm 7: rounds needed against Gröbner basis attacks, with a 50% security margin.
+fn get_number_of_rounds(width: usize, capacity: usize, security_level: usize, alpha: u64) -> usize {
+    let rate = width - capacity;
+    let dcon = |n: usize| ((alpha as usize - 1) * width * (n - 1)) / 2 + 2;
+    let v = |n: usize| width * (n - 1) + rate;
+    let target = BigUint::from(1u32) << security_level;
+
+    let l1 = (1..25)
+        .find(|&n| binomial(v(n) + dcon(n), v(n)).pow(2) > target)
+        .unwrap_or(25);
+    (3 * l1.max(5)).div_ceil(2)
+}
+
+fn binomial(n: usize, k: usize) -> BigUint {
+    (0..k).fold(BigUint::from(1u32), |acc, i| acc * (n - i) / (i + 1))
+}
+
+/// Algorithm 5: round constants squeezed from SHAKE256 seeded with the instance description.
+fn get_round_constants<F: RescueField>(
+    modulus: &BigUint,
+    width: usize,
+    capacity: usize,
+    security_level: usize,
+    rounds: usize,
+) -> Vec<Vec<F>> {
+    let bytes_per_int = (modulus.bits() as usize).div_ceil(8) + 1;
+    let seed = format!(
+        "Rescue-XLIX({},{},{},{})",
+        modulus, width, capacity, security_level
+    );
+    let mut reader = shake256(&seed);
+
+    let mut chunk = vec![0u8; bytes_per_int];
+    (0..rounds)
+        .map(|_| {
+            (0..2 * width)
+                .map(|_| {
+                    reader.read(&mut chunk);
+                    F::from_le_bytes_mod_order(&chunk)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Smallest primitive element of `F`: the smallest `g` such that no `g^((p - 1) / q)` is one
+/// for a prime factor `q` of `p - 1`.
+pub fn primitive_element<F: RescueField>() -> F {
+    let order = F::modulus() - 1u32;
+    (2u32..)
+        .map(|g| F::from_biguint(&BigUint::from(g)))
+        .find(|&g| {
+            F::order_factors()
+                .iter()
+                .all(|&q| g.power(&(&order / q).to_u64_digits()) != F::ONE)
+        })
+        .unwrap()
+}
+
+/// Algorithm 6: the MDS matrix is the transposed right half of the reduced echelon form of a
+/// `width x 2 * width` Vandermonde matrix over the smallest primitive element.
+fn get_mds_matrix<F: RescueField>(width: usize) -> Vec<Vec<F>> {
+    let g = primitive_element::<F>();
+    let mut v: Vec<Vec<F>> = (0..width)
+        .map(|i| (0..2 * width).map(|j| g.power(&[(i * j) as u64])).collect())
+        .collect();
+
+    // Gauss-Jordan elimination; the left half is a Vandermonde matrix over distinct powers of
+    // a generator, so it is invertible and every pivot is non-zero
+    for col in 0..width {
+        let pivot = (col..width).find(|&row| v[row][col] != F::ZERO).unwrap();
+        v.swap(col, pivot);
+        let inv = v[col][col].invert();
+        for x in v[col].iter_mut() {
+            *x *= inv;
+        }
+        let pivot_row = v[col].clone();
+        for (row, values) in v.iter_mut().enumerate() {
+            if row != col {
+                let factor = values[col];
+                for (x, &p) in values.iter_mut().zip(&pivot_row) {
+                    *x -= factor * p;
+                }
+            }
+        }
+    }
+
+    (0..width)
+        .map(|i| (0..width).map(|j| v[j][width + i]).collect())
+        .collect()
+}
+
+// SPONGE
+// ================================================================================================
+
+/// Rescue sponge over `F`, with 10* padding and the domain in the first capacity element.
+#[derive(Clone)]
+pub struct Rescue<F: RescueField> {
+    params: RescueParameters<F>,
+    state: Vec<F>,
+    idx: usize,
+    squeezing: bool,
+}
+
+impl<F: RescueField> Rescue<F> {
+    /// Returns a new hasher for the default domain.
+    pub fn new(params: &RescueParameters<F>) -> Self {
+        Self::with_domain(params, F::ZERO)
+    }
+
+    /// Returns a new hasher with `domain` written into the first capacity element.
+    pub fn with_domain(params: &RescueParameters<F>, domain: F) -> Self {
+        let mut state = vec![F::ZERO; params.width];
+        state[params.rate] = domain;
+        Rescue {
+            params: params.clone(),
+            state,
+            idx: 0,
+            squeezing: false,
+        }
+    }
+
+    /// Absorbs data into the hasher state.
+    pub fn update(&mut self, data: &[F]) {
+        if self.squeezing {
+            self.squeezing = false;
+            self.idx = 0;
+        }
+        for &element in data {
+            self.state[self.idx] += element;
+            self.idx += 1;
+            if self.idx == self.params.rate {
+                apply_permutation(&self.params, &mut self.state);
+                self.idx = 0;
+            }
+        }
+    }
+
+    /// Returns the next `num_elements` elements of the rate. The first squeeze after absorbing
+    /// applies 10* padding.
+    pub fn squeeze(&mut self, num_elements: usize) -> Vec<F> {
+        if !self.squeezing {
+            self.state[self.idx] += F::ONE;
+            apply_permutation(&self.params, &mut self.state);
+            self.squeezing = true;
+            self.idx = 0;
+        }
+        (0..num_elements)
+            .map(|_| {
+                if self.idx == self.params.rate {
+                    apply_permutation(&self.params, &mut self.state);
+                    self.idx = 0;
+                }
+                self.idx += 1;
+                self.state[self.idx - 1]
+            })
+            .collect()
+    }
+
+    /// Returns hash of the data absorbed into the hasher, after 10* padding. The digest is
+    /// the first [RescueParameters::capacity] elements of the state.
+    pub fn finalize(mut self) -> Vec<F> {
+        let capacity = self.params.capacity();
+        self.squeeze(capacity)
+    }
+
+    /// Returns hash of the provided data.
+    pub fn digest(params: &RescueParameters<F>, data: &[F]) -> Vec<F> {
+        let mut hasher = Self::new(params);
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+impl<F: RescueField> AlgebraicSponge<F> for Rescue<F> {
+    type Parameters = RescueParameters<F>;
+
+    fn with_domain(params: &RescueParameters<F>, domain: F) -> Self {
+        Rescue::with_domain(params, domain)
+    }
+
+    fn absorb(&mut self, elements: &[F]) {
+        self.update(elements)
+    }
+
+    fn squeeze(&mut self, num_elements: usize) -> Vec<F> {
+        Rescue::squeeze(self, num_elements)
+    }
+}
+
+/// Number of bytes packed into one element of `F`, so that packing never wraps the modulus.
+pub fn bytes_per_element<F: RescueField>() -> usize {
+    (F::modulus().bits() as usize - 1) / 8
+}
+
+/// Canonical encoding of bytes into elements of `F`: a 0x01 terminator is appended, then the
+/// bytes are packed little-endian into elements of [bytes_per_element] bytes each.
+pub fn bytes_to_elements<F: RescueField>(bytes: &[u8]) -> Vec<F> {
+    let mut padded = bytes.to_vec();
+    padded.push(1);
+    padded
+        .chunks(bytes_per_element::<F>())
+        .map(F::from_le_bytes_mod_order)
+        .collect()
+}
+
+// RESCUE PERMUTATION
+// ================================================================================================
+
+/// Applies Rescue-XLIX permutation to the provided state.
+pub fn apply_permutation<F: RescueField>(params: &RescueParameters<F>, state: &mut [F]) {
+    for ark in params.ark.iter() {
+        // apply first half of Rescue round
+        for x in state.iter_mut() {
+            *x = x.power(&[params.alpha]);
+        }
+        apply_mds(params, state);
+        for (x, &c) in state.iter_mut().zip(&ark[..params.width]) {
+            *x += c;
+        }
+
+        // apply second half of Rescue round
+        for x in state.iter_mut() {
+            *x = x.power(&params.inv_alpha);
+        }
+        apply_mds(params, state);
+        for (x, &c) in state.iter_mut().zip(&ark[params.width..]) {
+            *x += c;
+        }
+    }
+}
+
+fn apply_mds<F: RescueField>(params: &RescueParameters<F>, state: &mut [F]) {
+    let result: Vec<F> = params
+        .mds
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(state.iter())
+                .fold(F::ZERO, |acc, (&m, &x)| acc + m * x)
+        })
+        .collect();
+    state.copy_from_slice(&result);
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Fr as Bls12_377Fr;
+    use ark_bls12_381::Fr as Bls12_381Fr;
+
+    /// Miller-Rabin with the bases which are deterministic below 2^64.
+    fn is_prime(n: u64) -> bool {
+        let bases = [2u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+        if bases.contains(&n) {
+            return true;
+        }
+        if n < 2 || bases.iter().any(|&p| n.is_multiple_of(p)) {
+            return false;
+        }
+        let (n, one) = (BigUint::from(n), BigUint::from(1u32));
+        let minus_one = &n - 1u32;
+        let twos = minus_one.trailing_zeros().unwrap();
+        let odd = &minus_one >> twos;
+        bases.iter().all(|&base| {
+            let mut x = BigUint::from(base).modpow(&odd, &n);
+            if x == one || x == minus_one {
+                return true;
+            }
+            (1..twos).any(|_| {
+                x = x.modpow(&BigUint::from(2u32), &n);
+                x == minus_one
+            })
+        })
+    }
+
+    /// Checks that the listed factors are the prime factors of `p - 1` and returns the smallest
+    /// primitive element as an integer.
+    fn smallest_primitive_element<F: RescueField>() -> BigUint {
+        let mut order = F::modulus() - 1u32;
+        for &q in F::order_factors() {
+            assert!(is_prime(q), "{} is not prime", q);
+            assert_eq!(&order % q, BigUint::from(0u32));
+            while &order % q == BigUint::from(0u32) {
+                order /= q;
+            }
+        }
+        assert_eq!(order, BigUint::from(1u32));
+        primitive_element::<F>().to_biguint()
+    }
+
+    #[test]
+    fn primitive_elements_are_smallest() {
+        assert_eq!(
+            smallest_primitive_element::<Bls12_377Fr>(),
+            BigUint::from(22u32)
+        );
+        assert_eq!(
+            smallest_primitive_element::<Bls12_381Fr>(),
+            BigUint::from(7u32)
+        );
+        #[cfg(feature = "stark")]
+        assert_eq!(
+            smallest_primitive_element::<winterfell::math::fields::f128::BaseElement>(),
+            BigUint::from(3u32)
+        );
+    }
+}
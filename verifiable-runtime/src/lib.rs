@@ -2,4 +2,13 @@ mod runtime;
 pub use runtime::dvm;
 pub use runtime::opcode;
 
+pub mod backend;
+pub mod hash;
 pub mod snark;
+pub mod transcript;
+
+#[cfg(feature = "stark")]
+pub mod utils;
+
+#[cfg(feature = "stark")]
+pub mod stark;
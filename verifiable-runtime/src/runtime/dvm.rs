@@ -1,4 +1,5 @@
 use super::opcode::{BinaryCode, Opcode};
+use std::fmt;
 
 // Context of Dummy Virtual Machine
 pub struct DVMContext {
@@ -13,6 +14,12 @@ pub struct DVM {
     context: DVMContext,
 }
 
+impl Default for DVM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DVM {
     // Create new instance of DVM with default context
     pub fn new() -> Self {
@@ -51,3 +58,85 @@ impl DVM {
         self.context.result
     }
 }
+
+// Reason a program can not be executed, reported by `evaluate` where `DVM::process` panics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    // The opcode at `pc` needs more words than the stack holds
+    StackUnderflow { pc: usize, opcode: BinaryCode },
+    // DIV at the given offset divides by zero
+    DivisionByZero(usize),
+    // The byte at `pc` is not an opcode
+    InvalidOpcode { pc: usize, byte: u8 },
+    // PUSH at the given offset has fewer than four parameter bytes
+    TruncatedPush(usize),
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StackUnderflow { pc, opcode } => {
+                write!(f, "{} at {} underflows the stack", opcode.mnemonic(), pc)
+            }
+            Self::DivisionByZero(pc) => write!(f, "DIV at {} divides by zero", pc),
+            Self::InvalidOpcode { pc, byte } => write!(f, "invalid opcode {:#04x} at {}", byte, pc),
+            Self::TruncatedPush(pc) => write!(f, "PUSH at {} has no 4-byte parameter", pc),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+// Returns the result of `program` like `DVM::process`, without printing the execution, and an
+// error instead of a panic when it can not run. Words wrap on overflow, as in the circuits.
+pub fn evaluate(program: &[u8]) -> Result<i32, ExecutionError> {
+    let mut stack = Vec::<i32>::new();
+    let mut result = 0;
+    let mut pc = 0;
+    while pc < program.len() {
+        let opcode = BinaryCode::from(program[pc]);
+        let underflow = ExecutionError::StackUnderflow { pc, opcode };
+        match opcode {
+            BinaryCode::Add | BinaryCode::Sub | BinaryCode::Mul | BinaryCode::Div => {
+                if stack.len() < 2 {
+                    return Err(underflow);
+                }
+                let (b, a) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(match opcode {
+                    BinaryCode::Add => a.wrapping_add(b),
+                    BinaryCode::Sub => a.wrapping_sub(b),
+                    BinaryCode::Mul => a.wrapping_mul(b),
+                    _ if b == 0 => return Err(ExecutionError::DivisionByZero(pc)),
+                    _ => a.wrapping_div(b),
+                });
+            }
+            BinaryCode::Push => {
+                let param = program
+                    .get(pc + 1..pc + 5)
+                    .ok_or(ExecutionError::TruncatedPush(pc))?;
+                stack.push(i32::from_be_bytes(param.try_into().unwrap()));
+                pc += 4;
+            }
+            BinaryCode::Pop => {
+                stack.pop().ok_or(underflow)?;
+            }
+            BinaryCode::Ret => result = stack.pop().ok_or(underflow)?,
+            BinaryCode::Swap => {
+                if stack.len() < 2 {
+                    return Err(underflow);
+                }
+                let n = stack.len();
+                stack.swap(n - 1, n - 2);
+            }
+            BinaryCode::Stop => {}
+            BinaryCode::Invalid => {
+                return Err(ExecutionError::InvalidOpcode {
+                    pc,
+                    byte: program[pc],
+                })
+            }
+        }
+        pc += 1;
+    }
+    Ok(result)
+}
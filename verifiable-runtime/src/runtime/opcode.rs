@@ -1,7 +1,7 @@
 use super::dvm::DVMContext;
 
 // Operation Code in binary form
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 // DVM's opcode
 pub enum BinaryCode {
     Add = 0x01,
@@ -107,6 +107,22 @@ impl BinaryCode {
         }
     }
 
+    // Assembly name of the opcode, as printed while executing it
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            Self::Add => "ADD",
+            Self::Sub => "SUB",
+            Self::Mul => "MUL",
+            Self::Div => "DIV",
+            Self::Push => "PUSH",
+            Self::Pop => "POP",
+            Self::Ret => "RET",
+            Self::Swap => "SWAP",
+            Self::Stop => "STOP",
+            Self::Invalid => "INVALID",
+        }
+    }
+
     pub fn to(&self) -> u8 {
         match *self {
             Self::Add => 0x01,
@@ -134,4 +150,37 @@ impl Opcode {
     pub fn exec(&self, ctx: &mut DVMContext) {
         self.0.exec(ctx, self.1)
     }
+
+    pub fn code(&self) -> BinaryCode {
+        self.0
+    }
+
+    pub fn param(&self) -> i32 {
+        self.1
+    }
+}
+
+// Decode a program into its opcodes, each paired with the program counter it starts at
+pub fn decode(program: &[u8]) -> Vec<(usize, Opcode)> {
+    let mut opcodes = Vec::new();
+    let mut program_ptr = 0;
+    while program_ptr < program.len() {
+        let bin_code = BinaryCode::from(program[program_ptr]);
+        match bin_code {
+            BinaryCode::Push => {
+                let param = i32::from_be_bytes(
+                    program[program_ptr + 1..program_ptr + 5]
+                        .try_into()
+                        .unwrap(),
+                );
+                opcodes.push((program_ptr, Opcode::new(bin_code, param)));
+                program_ptr += 5;
+            }
+            _ => {
+                opcodes.push((program_ptr, Opcode::new(bin_code, 0)));
+                program_ptr += 1;
+            }
+        }
+    }
+    opcodes
 }
@@ -6,44 +6,90 @@
     stable_features,
     non_shorthand_field_patterns,
     renamed_and_removed_lints,
-    private_in_public,
     unsafe_code
 )]
 
-use crate::opcode::BinaryCode;
+use crate::hash::rescue::{
+    bytes_to_elements,
+    constraints::{self, RescueGadget},
+    Rescue, RescueField, RescueParameters,
+};
+use crate::opcode::{decode, BinaryCode, Opcode};
+use crate::snark::debug::{Checkpoint, StepState, SynthesisLog};
 
 // Bring in some tools for using pairing-friendly curves
 // We're going to use the BLS12-377 pairing-friendly elliptic curve.
 use ark_bls12_377::{Bls12_377, Fr};
-use ark_ff::{PrimeField, ToBytes};
-use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, prelude::FieldVar};
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    bits::{boolean::Boolean, uint32::UInt32, uint8::UInt8, ToBitsGadget},
+    eq::EqGadget,
+    fields::fp::FpVar,
+    prelude::{CondSelectGadget, FieldVar},
+};
 use ark_std::test_rng;
 
 // We'll use these interfaces to construct our circuit.
-use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_relations::r1cs::{info_span, ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+
+/// Domain tag of program commitments, keeping them apart from other uses of [Rescue]
+pub const PROGRAM_DOMAIN: u64 = 2;
+
+/// Rescue instance used for program commitments: a width-3 state with one capacity element
+/// at 128-bit security.
+pub fn commitment_parameters<F: PrimeField + RescueField>() -> RescueParameters<F> {
+    RescueParameters::new(3, 1, 128)
+}
+
+/// Rescue hash of `program` and the `result` it returns. This is the single public input of
+/// `DVMCircuit`, so the program and the result stay hidden behind it; the keys of the circuit
+/// only depend on the length of the program, see [pad_program].
+pub fn program_commitment<F: PrimeField + RescueField>(program: &[u8], result: i32) -> F {
+    let mut hasher = Rescue::with_domain(&commitment_parameters(), F::from(PROGRAM_DOMAIN));
+    hasher.update(&bytes_to_elements(program));
+    hasher.update(&[to_prime_field_value(result)]);
+    hasher.finalize()[0]
+}
+
+/// Pads `program` with STOP, which has no effect, to `len` bytes, so programs of up to `len`
+/// bytes are proved with the keys of one circuit.
+pub fn pad_program(program: &[u8], len: usize) -> Vec<u8> {
+    assert!(
+        program.len() <= len,
+        "program of {} bytes does not fit into {} bytes",
+        program.len(),
+        len
+    );
+    let mut padded = program.to_vec();
+    padded.resize(len, BinaryCode::Stop.to());
+    padded
+}
 
 /// This is our DVM circuit for proving state of DVM
-struct DVMCircuit<F: PrimeField> {
+pub struct DVMCircuit<F: PrimeField + RescueField> {
     program: Vec<u8>,
     result: F,
+    commitment: F,
 }
 
 /// Constructor for DVMCircuit
-#[allow(dead_code)]
-impl<F: PrimeField> DVMCircuit<F> {
+impl<F: PrimeField + RescueField> DVMCircuit<F> {
     pub fn new(program: Vec<u8>, result: i32) -> Self {
         Self {
+            commitment: program_commitment(&program, result),
             program,
-            result: F::from(result as u32),
+            result: to_prime_field_value(result),
         }
     }
 }
 
-impl<F: PrimeField> Clone for DVMCircuit<F> {
+impl<F: PrimeField + RescueField> Clone for DVMCircuit<F> {
     fn clone(&self) -> Self {
         DVMCircuit {
             program: self.program.clone(),
-            result: self.result.clone(),
+            result: self.result,
+            commitment: self.commitment,
         }
     }
 }
@@ -51,117 +97,404 @@ impl<F: PrimeField> Clone for DVMCircuit<F> {
 /// Our DVM circuit implements this `Circuit` trait which
 /// is used during paramgen and proving in order to
 /// synthesize the constraint system.
-impl<'a, F: PrimeField> ConstraintSynthesizer<F> for DVMCircuit<F> {
+impl<F: PrimeField + RescueField> ConstraintSynthesizer<F> for DVMCircuit<F> {
     fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
-        let mut program_ptr = 0;
+        self.synthesize(cs, &mut SynthesisLog::default())
+    }
+}
+
+impl<F: PrimeField + RescueField> DVMCircuit<F> {
+    /// Generates the constraints of the circuit, recording in `log` where each part of them
+    /// starts.
+    ///
+    /// Every byte of the program is a step of the circuit which runs all the opcodes on the
+    /// top of the stack and keeps the effect of the one the byte holds, so the constraints only
+    /// depend on the length of the program. Steps of the parameter bytes of `PUSH` and of
+    /// opcodes without effect leave the stack as it is.
+    pub(crate) fn synthesize(
+        self,
+        cs: ConstraintSystemRef<F>,
+        log: &mut SynthesisLog,
+    ) -> Result<(), SynthesisError> {
         let program = self.program;
-        let mut stack = Vec::<i32>::new();
-
-        while program_ptr < program.len() {
-            let bin_code = BinaryCode::from(program[program_ptr]);
-            match bin_code {
-                BinaryCode::Push => {
-                    program_ptr += 1;
-                    let param = i32::from_be_bytes(
-                        program.as_slice()[program_ptr..program_ptr + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    program_ptr += 4;
-                    stack.push(param);
-                }
-                BinaryCode::Add => {
-                    let b_val = stack.pop().unwrap() as u32;
-                    let a_val = stack.pop().unwrap() as u32;
-                    let a = FpVar::new_witness(cs.clone(), || Ok(F::from(a_val))).unwrap();
-                    let b = FpVar::new_witness(cs.clone(), || Ok(F::from(b_val))).unwrap();
-                    let c_val = a_val + b_val;
-                    let c_t = a + b;
-                    let c = FpVar::new_witness(cs.clone(), || Ok(F::from(c_val))).unwrap();
-                    c.enforce_equal(&c_t)?;
-                    stack.push(a_val as i32 + b_val as i32);
-                    program_ptr += 1;
-                }
-                BinaryCode::Sub => {
-                    let b_val = stack.pop().unwrap() as u32;
-                    let a_val = stack.pop().unwrap() as u32;
-                    let a = FpVar::new_witness(cs.clone(), || Ok(F::from(a_val))).unwrap();
-                    let b = FpVar::new_witness(cs.clone(), || Ok(F::from(b_val))).unwrap();
-                    if a_val > b_val {
-                        let c_val = a_val - b_val;
-                        let c_t = a - b;
-                        let c = FpVar::new_witness(cs.clone(), || Ok(F::from(c_val))).unwrap();
-                        c.enforce_equal(&c_t)?;
-                    } else {
-                        let c_val = b_val - a_val;
-                        let c_t = b - a;
-                        let c = FpVar::new_witness(cs.clone(), || Ok(F::from(c_val))).unwrap();
-                        c.enforce_equal(&c_t)?;
-                    }
-                    stack.push(a_val as i32 - b_val as i32);
-                    program_ptr += 1;
-                }
-                BinaryCode::Mul => {
-                    let b_val = stack.pop().unwrap() as u32;
-                    let a_val = stack.pop().unwrap() as u32;
-                    let a = FpVar::new_witness(cs.clone(), || Ok(F::from(a_val))).unwrap();
-                    let b = FpVar::new_witness(cs.clone(), || Ok(F::from(b_val))).unwrap();
-                    let c_val = a_val * b_val;
-                    let c_t = a * b;
-                    let c = FpVar::new_witness(cs.clone(), || Ok(F::from(c_val))).unwrap();
-                    c.enforce_equal(&c_t)?;
-                    stack.push(a_val as i32 * b_val as i32);
-                    program_ptr += 1;
-                }
-                BinaryCode::Div => {
-                    let b_val = stack.pop().unwrap() as u32;
-                    let a_val = stack.pop().unwrap() as u32;
-                    let a = FpVar::new_witness(cs.clone(), || Ok(F::from(a_val))).unwrap();
-                    let b = FpVar::new_witness(cs.clone(), || Ok(F::from(b_val))).unwrap();
-                    let c_val = a_val / b_val;
-                    let c_t = a.mul_by_inverse(&b).unwrap();
-                    let c = FpVar::new_witness(cs.clone(), || Ok(F::from(c_val))).unwrap();
-                    c.enforce_equal(&c_t)?;
-                    stack.push(c_val as i32);
-                    program_ptr += 1;
-                }
-                BinaryCode::Pop => {
-                    stack.pop();
-                    program_ptr += 1;
-                }
-                BinaryCode::Swap => {
-                    let a_val = stack.pop().unwrap() as u32;
-                    let b_val = stack.pop().unwrap() as u32;
-                    let a = FpVar::new_witness(cs.clone(), || Ok(F::from(a_val))).unwrap();
-                    let b = FpVar::new_witness(cs.clone(), || Ok(F::from(b_val))).unwrap();
-                    stack.push(a_val as i32);
-                    stack.push(b_val as i32);
-                    let n = stack.len();
-                    let a_new_val = stack[n - 2] as u32;
-                    let b_new_val = stack[n - 1] as u32;
-                    let a_t = FpVar::new_witness(cs.clone(), || Ok(F::from(a_new_val))).unwrap();
-                    let b_t = FpVar::new_witness(cs.clone(), || Ok(F::from(b_new_val))).unwrap();
-                    b.enforce_equal(&b_t)?;
-                    a.enforce_equal(&a_t)?;
-                    program_ptr += 1;
-                }
-                BinaryCode::Ret => {
-                    let result_val = stack.pop().unwrap() as u32;
-                    let result_target =
-                        FpVar::new_witness(cs.clone(), || Ok(F::from(result_val))).unwrap();
-                    let result = FpVar::new_input(cs.clone(), || Ok(F::from(self.result))).unwrap();
-                    result.enforce_equal(&result_target)?;
-                    program_ptr += 1;
-                }
-                _ => {
-                    program_ptr += 1;
+        let opcodes = decode(&program);
+        let depth = stack_depth(program.len());
+        // the native values of the stack, top last, fill in the witnesses and the log
+        let mut stack = Stack::empty(depth);
+        let mut values = Vec::<i32>::new();
+
+        // The program bytes and the result are private witnesses, only their commitment is
+        // public.
+        let program_bytes = UInt8::new_witness_vec(cs.clone(), &program)?;
+        let result = FpVar::new_witness(cs.clone(), || Ok(self.result))?;
+        let flags = decode_flags(&program_bytes)?;
+        // whether a RET follows each byte, so the last RET sets the result of the program
+        let mut later_ret = vec![Boolean::FALSE; program.len() + 1];
+        for pc in (0..program.len()).rev() {
+            later_ret[pc] = later_ret[pc + 1].or(&flags[pc].ret)?;
+        }
+        // a program which never returns has the result zero
+        result.conditional_enforce_equal(&FpVar::zero(), &later_ret[0].not())?;
+
+        for (step, (pc, opcode)) in opcodes.iter().enumerate() {
+            let pc = *pc;
+            let bin_code = opcode.code();
+            log.steps.push((
+                Checkpoint::of(&cs),
+                StepState {
+                    pc,
+                    opcode: bin_code,
+                    stack: values.clone(),
+                },
+            ));
+
+            // Every constraint of the step is traced under its program counter and opcode
+            let span = info_span!(
+                target: "r1cs",
+                "step",
+                pc = pc,
+                opcode = bin_code.mnemonic()
+            );
+            let _step = span.enter();
+
+            // the opcode and, for PUSH, its parameter bytes
+            let next = opcodes
+                .get(step + 1)
+                .map_or(program.len(), |(next, _)| *next);
+            for byte in pc..next {
+                let operand = |i: usize| values.len().checked_sub(i + 1).map_or(0, |i| values[i]);
+                let native = Operands {
+                    a: operand(1),
+                    b: operand(0),
+                    // the divisor of the other steps is one, so it is never zero
+                    divides: byte == pc && bin_code == BinaryCode::Div,
+                };
+                let returned = stack.words[0].clone();
+                stack = execute(
+                    cs.clone(),
+                    &flags[byte],
+                    &pushed_word(&program_bytes, byte)?,
+                    &stack,
+                    native,
+                )?;
+                returned.conditional_enforce_equal(
+                    &result,
+                    &flags[byte].ret.and(&later_ret[byte + 1].not())?,
+                )?;
+                // the following bytes of the step run on the stack the opcode leaves
+                if byte == pc {
+                    execute_native(&mut values, opcode);
                 }
-            };
+            }
         }
+
+        log.commitment = Checkpoint::of(&cs);
+        let mut hasher =
+            RescueGadget::with_domain(&commitment_parameters(), F::from(PROGRAM_DOMAIN));
+        hasher.update(&constraints::bytes_to_elements(&program_bytes)?)?;
+        hasher.update(&[result])?;
+        let commitment = FpVar::new_input(cs, || Ok(self.commitment))?;
+        hasher.finalize()?[0].enforce_equal(&commitment)?;
         Ok(())
     }
 }
 
+/// Executes `opcode` on the native `values` of the stack, top last.
+fn execute_native(values: &mut Vec<i32>, opcode: &Opcode) {
+    let bin_code = opcode.code();
+    match bin_code {
+        BinaryCode::Push => values.push(opcode.param()),
+        BinaryCode::Add | BinaryCode::Sub | BinaryCode::Mul | BinaryCode::Div => {
+            if values.len() < 2 {
+                panic!(
+                    "Can not perform {}, stack deep is {}",
+                    bin_code.mnemonic(),
+                    values.len()
+                );
+            }
+            let (b, a) = (values.pop().unwrap(), values.pop().unwrap());
+            values.push(match bin_code {
+                BinaryCode::Add => a.wrapping_add(b),
+                BinaryCode::Sub => a.wrapping_sub(b),
+                BinaryCode::Mul => a.wrapping_mul(b),
+                _ => a.wrapping_div(b),
+            });
+        }
+        BinaryCode::Pop | BinaryCode::Ret => {
+            values.pop().unwrap();
+        }
+        BinaryCode::Swap => {
+            if values.len() < 2 {
+                panic!("Can not perform SWAP, stack deep is {}", values.len());
+            }
+            let n = values.len();
+            values.swap(n - 1, n - 2);
+        }
+        _ => {}
+    }
+}
+
+/// Number of stack slots of the circuit for programs of `len` bytes, which holds every word
+/// they can push as each PUSH takes five bytes; the arithmetic opcodes read two slots.
+fn stack_depth(len: usize) -> usize {
+    (len / 5).max(2)
+}
+
+// STEP GADGETS
+// ================================================================================================
+
+/// Opcode executed at one byte of the program: all flags are false on the parameter bytes of
+/// `PUSH` and on the opcodes without effect, STOP and invalid bytes.
+struct OpcodeFlags<F: PrimeField> {
+    start: Boolean<F>,
+    add: Boolean<F>,
+    sub: Boolean<F>,
+    mul: Boolean<F>,
+    div: Boolean<F>,
+    push: Boolean<F>,
+    pop: Boolean<F>,
+    ret: Boolean<F>,
+    swap: Boolean<F>,
+}
+
+/// Decodes the program in the circuit: the first byte starts an opcode, and so does the byte
+/// after each opcode, skipping the four parameter bytes of `PUSH`.
+fn decode_flags<F: PrimeField>(bytes: &[UInt8<F>]) -> Result<Vec<OpcodeFlags<F>>, SynthesisError> {
+    let mut flags: Vec<OpcodeFlags<F>> = Vec::with_capacity(bytes.len());
+    for (pc, byte) in bytes.iter().enumerate() {
+        let start = match pc {
+            0 => Boolean::TRUE,
+            _ => {
+                let previous = &flags[pc - 1];
+                let start = previous.start.and(&previous.push.not())?;
+                match pc.checked_sub(5) {
+                    Some(push) => start.or(&flags[push].push)?,
+                    None => start,
+                }
+            }
+        };
+        let value = Boolean::le_bits_to_fp_var(&byte.to_bits_le()?)?;
+        let is = |bin_code: BinaryCode| -> Result<Boolean<F>, SynthesisError> {
+            start.and(&value.is_eq(&FpVar::constant(F::from(bin_code.to())))?)
+        };
+        let opcode = OpcodeFlags {
+            add: is(BinaryCode::Add)?,
+            sub: is(BinaryCode::Sub)?,
+            mul: is(BinaryCode::Mul)?,
+            div: is(BinaryCode::Div)?,
+            push: is(BinaryCode::Push)?,
+            pop: is(BinaryCode::Pop)?,
+            ret: is(BinaryCode::Ret)?,
+            swap: is(BinaryCode::Swap)?,
+            start,
+        };
+        // the parameter of a PUSH must be part of the program
+        if pc + 5 > bytes.len() {
+            opcode.push.enforce_equal(&Boolean::FALSE)?;
+        }
+        flags.push(opcode);
+    }
+    Ok(flags)
+}
+
+/// Word pushed by a PUSH at `pc`, zero when its parameter runs past the end of the program.
+fn pushed_word<F: PrimeField>(bytes: &[UInt8<F>], pc: usize) -> Result<FpVar<F>, SynthesisError> {
+    match bytes.get(pc + 1..pc + 5) {
+        Some(parameter) => word_to_fp(&word_from_bytes(parameter)?),
+        None => Ok(FpVar::zero()),
+    }
+}
+
+/// DVM words of the stack in the circuit, top first, with flags of the occupied slots
+struct Stack<F: PrimeField> {
+    words: Vec<FpVar<F>>,
+    occupied: Vec<FpVar<F>>,
+}
+
+impl<F: PrimeField> Stack<F> {
+    fn empty(depth: usize) -> Self {
+        Stack {
+            words: vec![FpVar::zero(); depth],
+            occupied: vec![FpVar::zero(); depth],
+        }
+    }
+}
+
+/// Native values of the top two words, `b` on top, to fill in the witnesses of a step
+#[derive(Clone, Copy)]
+struct Operands {
+    a: i32,
+    b: i32,
+    /// Whether the step executes DIV
+    divides: bool,
+}
+
+/// Executes one byte of the program on `stack` and returns the stack afterwards.
+fn execute<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    flags: &OpcodeFlags<F>,
+    pushed: &FpVar<F>,
+    stack: &Stack<F>,
+    native: Operands,
+) -> Result<Stack<F>, SynthesisError> {
+    let occupied = &stack.occupied;
+    let flag = |flag: &Boolean<F>| FpVar::from(flag.clone());
+    let (add, sub, mul, div) = (
+        flag(&flags.add),
+        flag(&flags.sub),
+        flag(&flags.mul),
+        flag(&flags.div),
+    );
+    let arithmetic = &add + &sub + &mul + &div;
+
+    // every arithmetic opcode runs on the top two words, and the flags keep the executed one
+    let a = to_word(cs.clone(), &stack.words[1], native.a)?;
+    let b = to_word(cs.clone(), &stack.words[0], native.b)?;
+    let product = multiply(cs.clone(), &a, &b, native.a, native.b)?;
+    let divisor = UInt32::conditionally_select(&flags.div, &b, &UInt32::constant(1))?;
+    let quotient = divide(
+        cs,
+        &a,
+        &divisor,
+        native.a,
+        if native.divides { native.b } else { 1 },
+    )?;
+    let sum = UInt32::addmany(&[a.clone(), b.clone()])?;
+    let difference = UInt32::addmany(&[a, negate(&b)?, UInt32::constant(1)])?;
+    let computed = add * word_to_fp(&sum)?
+        + sub * word_to_fp(&difference)?
+        + mul * word_to_fp(&product)?
+        + div * word_to_fp(&quotient)?;
+
+    // the operands must be on the stack, and PUSH must leave room for its word
+    let (push, swap) = (flag(&flags.push), flag(&flags.swap));
+    let drop = flag(&flags.pop) + flag(&flags.ret);
+    (&arithmetic + &swap).mul_equals(&(FpVar::one() - &occupied[1]), &FpVar::zero())?;
+    drop.mul_equals(&(FpVar::one() - &occupied[0]), &FpVar::zero())?;
+    push.mul_equals(&occupied[occupied.len() - 1], &FpVar::zero())?;
+
+    let shift = |slots: &[FpVar<F>], pushed: &FpVar<F>, computed: &FpVar<F>| {
+        shift_stack(slots, [&push, &swap, &drop, &arithmetic], pushed, computed)
+    };
+    Ok(Stack {
+        words: shift(&stack.words, pushed, &computed)?,
+        occupied: shift(occupied, &FpVar::one(), &FpVar::one())?,
+    })
+}
+
+/// Applies the stack effect of the flags of `push`, `swap`, `drop` for POP and RET and
+/// `arithmetic` to `slots`, top first: PUSH shifts in `pushed`, the arithmetic opcodes replace
+/// the top two slots by `computed`, POP and RET drop the top slot and SWAP exchanges the top
+/// two.
+fn shift_stack<F: PrimeField>(
+    slots: &[FpVar<F>],
+    [push, swap, drop, arithmetic]: [&FpVar<F>; 4],
+    pushed: &FpVar<F>,
+    computed: &FpVar<F>,
+) -> Result<Vec<FpVar<F>>, SynthesisError> {
+    let slot = |i: usize| slots.get(i).cloned().unwrap_or_else(FpVar::zero);
+    let shrink = drop + arithmetic;
+    let mut shifted = Vec::with_capacity(slots.len());
+    shifted.push(
+        slot(0)
+            + push * (pushed - slot(0))
+            + arithmetic * (computed - slot(0))
+            + (drop + swap) * (slot(1) - slot(0)),
+    );
+    shifted.push(slot(1) + (push + swap) * (slot(0) - slot(1)) + &shrink * (slot(2) - slot(1)));
+    for i in 2..slots.len() {
+        shifted.push(slot(i) + push * (slot(i - 1) - slot(i)) + &shrink * (slot(i + 1) - slot(i)));
+    }
+    Ok(shifted)
+}
+
+// WORD GADGETS
+// ================================================================================================
+
+/// Reads the big-endian parameter bytes of `PUSH` into a DVM word.
+fn word_from_bytes<F: PrimeField>(bytes: &[UInt8<F>]) -> Result<UInt32<F>, SynthesisError> {
+    let mut bits = Vec::with_capacity(32);
+    for byte in bytes.iter().rev() {
+        bits.extend(byte.to_bits_le()?);
+    }
+    Ok(UInt32::from_bits_le(&bits))
+}
+
+/// Splits a stack value into the bits of a word, checking that it is one.
+fn to_word<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    value: &FpVar<F>,
+    native: i32,
+) -> Result<UInt32<F>, SynthesisError> {
+    let word = UInt32::new_witness(cs, || Ok(native as u32))?;
+    word_to_fp(&word)?.enforce_equal(value)?;
+    Ok(word)
+}
+
+/// Field element holding the two's complement bits of a word, as in [program_commitment].
+fn word_to_fp<F: PrimeField>(word: &UInt32<F>) -> Result<FpVar<F>, SynthesisError> {
+    Boolean::le_bits_to_fp_var(&word.to_bits_le())
+}
+
+/// Flips every bit of a word, so `!b + 1` is the two's complement negation of `b`.
+fn negate<F: PrimeField>(word: &UInt32<F>) -> Result<UInt32<F>, SynthesisError> {
+    word.xor(&UInt32::constant(u32::MAX))
+}
+
+/// Wrapping product of two words: the full product fits into 64 witness bits far below the
+/// modulus, and the low half of them is the result.
+fn multiply<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    a: &UInt32<F>,
+    b: &UInt32<F>,
+    a_val: i32,
+    b_val: i32,
+) -> Result<UInt32<F>, SynthesisError> {
+    let product = u64::from(a_val as u32) * u64::from(b_val as u32);
+    let low = UInt32::new_witness(cs.clone(), || Ok(product as u32))?;
+    let high = UInt32::new_witness(cs, || Ok((product >> 32) as u32))?;
+
+    let mut bits = low.to_bits_le();
+    bits.extend(high.to_bits_le());
+    Boolean::le_bits_to_fp_var(&bits)?.enforce_equal(&(word_to_fp(a)? * word_to_fp(b)?))?;
+    Ok(low)
+}
+
+/// Signed division rounding towards zero, like the DVM: with the absolute values recomposed
+/// from the sign bits, `|a| = |q| * |b| + |r|` and `|b| = |r| + gap + 1` over 32-bit witnesses
+/// pin down `|q|`, which then takes the sign of `a / b`.
+fn divide<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    a: &UInt32<F>,
+    b: &UInt32<F>,
+    a_val: i32,
+    b_val: i32,
+) -> Result<UInt32<F>, SynthesisError> {
+    if b_val == 0 {
+        panic!("Divide by 0");
+    }
+    let remainder = a_val.wrapping_rem(b_val).unsigned_abs();
+    let gap = b_val.unsigned_abs() - remainder - 1;
+    let q = UInt32::new_witness(cs.clone(), || Ok(a_val.wrapping_div(b_val).unsigned_abs()))?;
+    let r = UInt32::new_witness(cs.clone(), || Ok(remainder))?;
+    let gap = UInt32::new_witness(cs, || Ok(gap))?;
+
+    let absolute = |word: &UInt32<F>| -> Result<(Boolean<F>, FpVar<F>), SynthesisError> {
+        let sign = word.to_bits_le()[31].clone();
+        let value = word_to_fp(word)?;
+        let negated = FpVar::constant(F::from(1u64 << 32)) - &value;
+        Ok((sign.clone(), sign.select(&negated, &value)?))
+    };
+    let (a_sign, a_abs) = absolute(a)?;
+    let (b_sign, b_abs) = absolute(b)?;
+    let (q_fp, r_fp) = (word_to_fp(&q)?, word_to_fp(&r)?);
+    b_abs.enforce_equal(&(r_fp.clone() + word_to_fp(&gap)? + FpVar::one()))?;
+    a_abs.enforce_equal(&(q_fp * &b_abs + r_fp))?;
+
+    let negative = UInt32::addmany(&[negate(&q)?, UInt32::constant(1)])?;
+    UInt32::conditionally_select(&a_sign.xor(&b_sign)?, &negative, &q)
+}
+
 fn to_prime_field_value<F: PrimeField>(v: i32) -> F {
     F::from(v as u32)
 }
@@ -184,10 +517,7 @@ pub fn verify_dvm_circuit_groth16(result: i32) {
 
     // Create parameters for our circuit
     let params = {
-        let c = DVMCircuit::<Fr> {
-            program: program.clone(),
-            result: to_prime_field_value(result),
-        };
+        let c = DVMCircuit::<Fr>::new(program.clone(), result);
 
         generate_random_parameters::<Bls12_377, _, _>(c, rng).unwrap()
     };
@@ -200,7 +530,128 @@ pub fn verify_dvm_circuit_groth16(result: i32) {
     // Create a groth16 proof with our parameters.
     let proof = create_random_proof(c, &params, rng).unwrap();
     println!("Proved DVM code with proof: {:?}", proof);
-    let result = to_prime_field_value(result);
-    assert!(verify_proof(&pvk, &proof, &[result]).unwrap());
+    let commitment = program_commitment::<Fr>(&program, result);
+    assert!(verify_proof(&pvk, &proof, &[commitment]).unwrap());
     println!("Verified proof!.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // push 6, push 7, mul, ret
+    const PROGRAM: [u8; 12] = [
+        0x05, 0x00, 0x00, 0x00, 0x06, 0x05, 0x00, 0x00, 0x00, 0x07, 0x03, 0x07,
+    ];
+
+    fn is_satisfied(circuit: DVMCircuit<Fr>) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn commitment_is_the_only_public_input() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        // the constant one plus the commitment
+        assert_eq!(cs.num_instance_variables(), 2);
+    }
+
+    #[test]
+    fn wrong_commitment_is_rejected() {
+        let mut circuit = DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42);
+        circuit.commitment = program_commitment(&PROGRAM, 43);
+        assert!(!is_satisfied(circuit));
+
+        let mut other = PROGRAM.to_vec();
+        other[4] = 0x08;
+        let mut circuit = DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42);
+        circuit.commitment = program_commitment(&other, 42);
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn debugger_locates_commitment_mismatch() {
+        use crate::snark::debug::{debug_dvm_circuit, ConstraintSource};
+
+        let mut circuit = DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42);
+        circuit.commitment = program_commitment(&PROGRAM, 43);
+        let report = debug_dvm_circuit(circuit).unwrap().unwrap();
+        assert_eq!(report.source, ConstraintSource::Commitment);
+    }
+
+    #[test]
+    fn wrong_result_is_rejected() {
+        assert!(!is_satisfied(DVMCircuit::new(PROGRAM.to_vec(), 43)));
+    }
+
+    #[test]
+    fn signed_arithmetic_is_satisfied() {
+        // PUSH -7, PUSH 2, DIV, PUSH 5, SUB, PUSH -3, MUL, RET
+        let mut program = Vec::new();
+        for (value, bin_code) in [(-7, None), (2, Some(BinaryCode::Div))]
+            .into_iter()
+            .chain([(5, Some(BinaryCode::Sub)), (-3, Some(BinaryCode::Mul))])
+        {
+            program.push(BinaryCode::Push.to());
+            program.extend_from_slice(&i32::to_be_bytes(value));
+            program.extend(bin_code.map(|bin_code| bin_code.to()));
+        }
+        program.push(BinaryCode::Ret.to());
+
+        assert_eq!(crate::dvm::DVM::new().process(program.clone()), 24);
+        assert!(is_satisfied(DVMCircuit::new(program.clone(), 24)));
+        assert!(!is_satisfied(DVMCircuit::new(program, -24)));
+    }
+
+    #[test]
+    fn malicious_witness_is_rejected() {
+        // a prover claiming 43 with a matching commitment replaces the product of MUL by 43
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut log = SynthesisLog::default();
+        DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 43)
+            .synthesize(cs.clone(), &mut log)
+            .unwrap();
+        let (mul, _) = &log.steps[2];
+        {
+            // the step starts with the bits of its two operands, followed by the low half of
+            // their product
+            let mut cs = cs.borrow_mut().unwrap();
+            for i in 0..32 {
+                cs.witness_assignment[mul.witnesses + 64 + i] = Fr::from((43u64 >> i) & 1);
+            }
+        }
+
+        // the forged product breaks the multiplication before the result is even compared
+        let unsatisfied = cs.which_is_unsatisfied().unwrap().unwrap();
+        let index = unsatisfied.parse::<usize>().unwrap();
+        assert!((mul.constraints..log.steps[3].0.constraints).contains(&index));
+    }
+
+    #[test]
+    fn programs_of_one_length_share_keys() {
+        use crate::backend::{Groth16, ProofSystem};
+        use crate::dvm::DVM;
+
+        // PUSH 6, PUSH 7, ADD, RET and PUSH 42, RET padded to the same length
+        let mut add = PROGRAM.to_vec();
+        add[10] = BinaryCode::Add.to();
+        let padded = pad_program(&[0x05, 0x00, 0x00, 0x00, 0x2a, 0x07], PROGRAM.len());
+
+        let (pk, vk) = Groth16
+            .setup(&DVMCircuit::new(PROGRAM.to_vec(), 42))
+            .unwrap();
+        for program in [PROGRAM.to_vec(), add, padded] {
+            let result = DVM::new().process(program.clone());
+            let public_inputs = Groth16::public_inputs(&program, result);
+            let circuit = DVMCircuit::new(program, result);
+            let proof = Groth16.prove(&pk, circuit, &public_inputs).unwrap();
+            assert!(Groth16.verify(&vk, &public_inputs, &proof).unwrap());
+        }
+    }
+}
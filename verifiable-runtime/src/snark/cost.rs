@@ -0,0 +1,270 @@
+//! Size and cost of proving a DVM program with [DVMCircuit], computed by synthesizing the
+//! circuit without running a Groth16 setup, to budget which programs are practical to prove.
+use super::debug::SynthesisLog;
+use super::DVMCircuit;
+use crate::dvm::{evaluate, ExecutionError};
+use crate::opcode::BinaryCode;
+use ark_bls12_377::{Fr, G1Affine, G2Affine};
+use ark_relations::r1cs::{ConstraintSystem, SynthesisError};
+use ark_serialize::CanonicalSerialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Groth16 proving time per constraint of [DVMCircuit] over BLS12-377, calibrated with a
+/// release build on a single thread of an Intel Xeon vCPU: programs padded to 12 to 96 bytes,
+/// 6258 to 51973 constraints, took between 95 and 128µs per constraint, the smaller circuits
+/// being slower as the fixed costs weigh more. Other machines and thread counts differ, so time
+/// one prove to get a measurement.
+pub const PROVE_NANOS_PER_CONSTRAINT: u64 = 120_000;
+
+/// Cost of all the steps executing one opcode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeCost {
+    pub opcode: BinaryCode,
+    /// Number of times the opcode is executed
+    pub steps: usize,
+    pub constraints: usize,
+    pub witnesses: usize,
+}
+
+/// Reason a [CostReport] can not be computed
+#[derive(Debug)]
+pub enum CostError {
+    /// The program does not run on the DVM, so the circuit has no witness
+    Execution(ExecutionError),
+    Synthesis(SynthesisError),
+}
+
+impl fmt::Display for CostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Execution(e) => write!(f, "program can not be executed: {}", e),
+            Self::Synthesis(e) => write!(f, "failed to synthesize the circuit: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CostError {}
+
+impl From<ExecutionError> for CostError {
+    fn from(e: ExecutionError) -> Self {
+        Self::Execution(e)
+    }
+}
+
+impl From<SynthesisError> for CostError {
+    fn from(e: SynthesisError) -> Self {
+        Self::Synthesis(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostReport {
+    /// Result of the program, which the circuit proves
+    pub result: i32,
+    pub constraints: usize,
+    pub witnesses: usize,
+    /// Public inputs, not counting the constant one
+    pub public_inputs: usize,
+    /// Per opcode costs, in order of first execution
+    pub opcodes: Vec<OpcodeCost>,
+    /// Constraints allocating and decoding the program bytes and the result
+    pub witness_constraints: usize,
+    /// Constraints recomputing the program commitment
+    pub commitment_constraints: usize,
+    /// Rough estimate of the Groth16 proving time, see [PROVE_NANOS_PER_CONSTRAINT]
+    pub prove_time: Duration,
+    /// Size of the compressed Groth16 proving key, in bytes
+    pub proving_key_size: usize,
+    /// Size of the compressed Groth16 verifying key, in bytes
+    pub verifying_key_size: usize,
+}
+
+/// Runs `program`, then synthesizes [DVMCircuit] for it and its result and reports its size
+/// and cost.
+pub fn cost_report(program: &[u8]) -> Result<CostReport, CostError> {
+    let result = evaluate(program)?;
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    let mut log = SynthesisLog::default();
+    DVMCircuit::<Fr>::new(program.to_vec(), result).synthesize(cs.clone(), &mut log)?;
+
+    let mut opcodes: Vec<OpcodeCost> = Vec::new();
+    for (step, start, end) in log.step_spans() {
+        let index = match opcodes.iter().position(|cost| cost.opcode == step.opcode) {
+            Some(index) => index,
+            None => {
+                opcodes.push(OpcodeCost {
+                    opcode: step.opcode,
+                    steps: 0,
+                    constraints: 0,
+                    witnesses: 0,
+                });
+                opcodes.len() - 1
+            }
+        };
+        opcodes[index].steps += 1;
+        opcodes[index].constraints += end.constraints - start.constraints;
+        opcodes[index].witnesses += end.witnesses - start.witnesses;
+    }
+
+    let constraints = cs.num_constraints();
+    let witnesses = cs.num_witness_variables();
+    let instances = cs.num_instance_variables();
+    let (proving_key_size, verifying_key_size) =
+        groth16_key_sizes(constraints, instances, witnesses);
+    let first_step = log
+        .steps
+        .first()
+        .map_or(log.commitment, |(start, _)| *start);
+
+    Ok(CostReport {
+        result,
+        constraints,
+        witnesses,
+        public_inputs: instances - 1,
+        opcodes,
+        witness_constraints: first_step.constraints,
+        commitment_constraints: constraints - log.commitment.constraints,
+        prove_time: Duration::from_nanos(PROVE_NANOS_PER_CONSTRAINT * constraints as u64),
+        proving_key_size,
+        verifying_key_size,
+    })
+}
+
+/// Returns the sizes of the compressed Groth16 proving and verifying keys of a circuit, which
+/// follow from the shape of the constraint system; `instances` includes the constant one.
+fn groth16_key_sizes(constraints: usize, instances: usize, witnesses: usize) -> (usize, usize) {
+    let g1 = G1Affine::default().serialized_size();
+    let g2 = G2Affine::default().serialized_size();
+    // vectors are prefixed with their length
+    let len = 0u64.serialized_size();
+    let variables = instances + witnesses;
+    let domain_size = (constraints + instances).next_power_of_two();
+
+    // alpha, beta, gamma and delta, plus one query element per public input
+    let verifying_key = g1 + 3 * g2 + len + instances * g1;
+    // beta and delta in G1, the A and B queries over all variables, the H query over the
+    // evaluation domain and the L query over the witnesses
+    let proving_key = verifying_key
+        + 2 * g1
+        + 3 * len
+        + variables * (2 * g1 + g2)
+        + len
+        + (domain_size - 1) * g1
+        + len
+        + witnesses * g1;
+    (proving_key, verifying_key)
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "result:            {}", self.result)?;
+        writeln!(f, "constraints:       {}", self.constraints)?;
+        writeln!(f, "witness variables: {}", self.witnesses)?;
+        writeln!(f, "public inputs:     {}", self.public_inputs)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<10}{:>8}{:>14}{:>12}",
+            "opcode", "steps", "constraints", "witnesses"
+        )?;
+        for cost in self.opcodes.iter() {
+            writeln!(
+                f,
+                "{:<10}{:>8}{:>14}{:>12}",
+                cost.opcode.mnemonic(),
+                cost.steps,
+                cost.constraints,
+                cost.witnesses
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<18}{:>14}",
+            "program witness", self.witness_constraints
+        )?;
+        writeln!(f, "{:<18}{:>14}", "commitment", self.commitment_constraints)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "estimated prove time: {:?} (rough, calibrated on one release-build thread)",
+            self.prove_time
+        )?;
+        writeln!(f, "proving key:          {} bytes", self.proving_key_size)?;
+        write!(f, "verifying key:        {} bytes", self.verifying_key_size)
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Groth16, ProofSystem};
+
+    // push 6, push 7, mul, ret
+    const PROGRAM: [u8; 12] = [
+        0x05, 0x00, 0x00, 0x00, 0x06, 0x05, 0x00, 0x00, 0x00, 0x07, 0x03, 0x07,
+    ];
+
+    #[test]
+    fn breakdown_adds_up() {
+        let report = cost_report(&PROGRAM).unwrap();
+        assert_eq!(report.result, 42);
+        assert_eq!(report.public_inputs, 1);
+
+        let opcodes: Vec<(BinaryCode, usize)> = report
+            .opcodes
+            .iter()
+            .map(|cost| (cost.opcode, cost.steps))
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                (BinaryCode::Push, 2),
+                (BinaryCode::Mul, 1),
+                (BinaryCode::Ret, 1)
+            ]
+        );
+
+        let steps: usize = report.opcodes.iter().map(|cost| cost.constraints).sum();
+        assert_eq!(
+            report.witness_constraints + steps + report.commitment_constraints,
+            report.constraints
+        );
+    }
+
+    #[test]
+    fn key_sizes_match_setup() {
+        let report = cost_report(&PROGRAM).unwrap();
+        let circuit = DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42);
+        let (pk, _) = Groth16.setup(&circuit).unwrap();
+        assert_eq!(pk.serialized_size(), report.proving_key_size);
+        assert_eq!(pk.vk.serialized_size(), report.verifying_key_size);
+    }
+
+    #[test]
+    fn invalid_programs_are_errors() {
+        let error = |program: &[u8]| match cost_report(program) {
+            Err(CostError::Execution(e)) => e,
+            other => panic!("expected an execution error, got {:?}", other),
+        };
+        assert_eq!(
+            error(&PROGRAM[5..]),
+            ExecutionError::StackUnderflow {
+                pc: 5,
+                opcode: BinaryCode::Mul
+            }
+        );
+        assert_eq!(error(&PROGRAM[..8]), ExecutionError::TruncatedPush(5));
+        assert_eq!(
+            error(&[0x05, 0x00, 0x00, 0x00, 0x01, 0x09]),
+            ExecutionError::InvalidOpcode { pc: 5, byte: 0x09 }
+        );
+        let mut division = PROGRAM.to_vec();
+        division[9] = 0x00;
+        division[10] = BinaryCode::Div.to();
+        assert_eq!(error(&division), ExecutionError::DivisionByZero(10));
+    }
+}
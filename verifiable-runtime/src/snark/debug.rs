@@ -0,0 +1,263 @@
+//! Debug mode for [DVMCircuit]: instead of a bare `SynthesisError` or a rejected proof, it
+//! reports the DVM step whose constraints do not hold, in the spirit of halo2's `MockProver`.
+use super::DVMCircuit;
+use crate::hash::rescue::RescueField;
+use crate::opcode::BinaryCode;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystem, ConstraintSystemRef, SynthesisError};
+use std::fmt;
+
+/// State of the DVM right before an opcode is executed
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepState {
+    /// Offset of the opcode in the program
+    pub pc: usize,
+    pub opcode: BinaryCode,
+    /// Stack contents, the top of the stack last
+    pub stack: Vec<i32>,
+}
+
+impl StepState {
+    /// Name of the step in reports, such as `step_10_MUL`
+    pub fn name(&self) -> String {
+        format!("step_{}_{}", self.pc, self.opcode.mnemonic())
+    }
+}
+
+/// Part of [DVMCircuit] an unsatisfied constraint belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintSource {
+    /// Allocation and decoding of the program bytes and the result
+    Witness,
+    /// Execution of one opcode
+    Step(StepState),
+    /// Recomputation of the program commitment
+    Commitment,
+    /// The constraint could not be located, e.g. because the constraint matrices are missing
+    Unknown,
+}
+
+impl ConstraintSource {
+    /// Name of the source in reports: `witness`, `commitment`, or the name of the step
+    pub fn name(&self) -> String {
+        match self {
+            Self::Witness => "witness".to_string(),
+            Self::Step(step) => step.name(),
+            Self::Commitment => "commitment".to_string(),
+            Self::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+/// First constraint of a [DVMCircuit] that is not satisfied
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsatisfiedConstraint {
+    /// Constraint as reported by `which_is_unsatisfied`: its index, or its trace when a
+    /// `ConstraintLayer` is installed
+    pub constraint: String,
+    /// Name of the part of the circuit holding the constraint, e.g. `step_11_RET`
+    pub name: String,
+    pub source: ConstraintSource,
+}
+
+impl fmt::Display for UnsatisfiedConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "constraint {} of {} is not satisfied",
+            self.constraint, self.name
+        )?;
+        match &self.source {
+            ConstraintSource::Step(step) => write!(f, " with stack {:?}", step.stack),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sizes of the constraint system when a part of the circuit starts
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Checkpoint {
+    pub(crate) constraints: usize,
+    pub(crate) witnesses: usize,
+}
+
+impl Checkpoint {
+    pub(crate) fn of<F: PrimeField>(cs: &ConstraintSystemRef<F>) -> Self {
+        Checkpoint {
+            constraints: cs.num_constraints(),
+            witnesses: cs.num_witness_variables(),
+        }
+    }
+}
+
+/// Where each part of a [DVMCircuit] starts, filled in during synthesis
+#[derive(Debug, Default)]
+pub(crate) struct SynthesisLog {
+    pub(crate) steps: Vec<(Checkpoint, StepState)>,
+    pub(crate) commitment: Checkpoint,
+}
+
+impl SynthesisLog {
+    /// Returns each step with the checkpoint it starts at and the one it ends at.
+    pub(crate) fn step_spans(&self) -> impl Iterator<Item = (&StepState, Checkpoint, Checkpoint)> {
+        let ends = self
+            .steps
+            .iter()
+            .skip(1)
+            .map(|(start, _)| *start)
+            .chain([self.commitment]);
+        self.steps
+            .iter()
+            .zip(ends)
+            .map(|((start, step), end)| (step, *start, end))
+    }
+
+    fn locate(&self, index: usize) -> ConstraintSource {
+        if index >= self.commitment.constraints {
+            return ConstraintSource::Commitment;
+        }
+        match self
+            .steps
+            .iter()
+            .rev()
+            .find(|(start, _)| start.constraints <= index)
+        {
+            Some((_, step)) => ConstraintSource::Step(step.clone()),
+            None => ConstraintSource::Witness,
+        }
+    }
+}
+
+/// Synthesizes `circuit` into a test constraint system and returns its first unsatisfied
+/// constraint, mapped back to the DVM step that generated it, or `None` when all constraints
+/// hold.
+pub fn debug_dvm_circuit<F: PrimeField + RescueField>(
+    circuit: DVMCircuit<F>,
+) -> Result<Option<UnsatisfiedConstraint>, SynthesisError> {
+    let cs = ConstraintSystem::<F>::new_ref();
+    let mut log = SynthesisLog::default();
+    circuit.synthesize(cs.clone(), &mut log)?;
+
+    Ok(cs.which_is_unsatisfied()?.map(|constraint| {
+        // with a `ConstraintLayer` installed the constraint is reported by its trace, so its
+        // index is looked up in the constraint matrices instead
+        let source = match constraint
+            .parse::<usize>()
+            .ok()
+            .or_else(|| first_unsatisfied(&cs))
+        {
+            Some(index) => log.locate(index),
+            None => ConstraintSource::Unknown,
+        };
+        UnsatisfiedConstraint {
+            constraint,
+            name: source.name(),
+            source,
+        }
+    }))
+}
+
+/// Returns the index of the first constraint of `cs` that does not hold.
+fn first_unsatisfied<F: PrimeField>(cs: &ConstraintSystemRef<F>) -> Option<usize> {
+    cs.inline_all_lcs();
+    let matrices = cs.to_matrices()?;
+    let cs = cs.borrow()?;
+    let assignment: Vec<F> = cs
+        .instance_assignment
+        .iter()
+        .chain(&cs.witness_assignment)
+        .copied()
+        .collect();
+    let eval = |row: &Vec<(F, usize)>| -> F {
+        row.iter()
+            .map(|&(coeff, var)| coeff * assignment[var])
+            .sum()
+    };
+    (0..matrices.num_constraints)
+        .find(|&i| eval(&matrices.a[i]) * eval(&matrices.b[i]) != eval(&matrices.c[i]))
+}
+
+/// Returns the number of constraints generated by each step of `circuit`, in execution order
+/// and named like `step_10_MUL`.
+pub fn step_constraint_counts<F: PrimeField + RescueField>(
+    circuit: DVMCircuit<F>,
+) -> Result<Vec<(String, usize)>, SynthesisError> {
+    let cs = ConstraintSystem::<F>::new_ref();
+    let mut log = SynthesisLog::default();
+    circuit.synthesize(cs, &mut log)?;
+
+    Ok(log
+        .step_spans()
+        .map(|(step, start, end)| (step.name(), end.constraints - start.constraints))
+        .collect())
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Fr;
+
+    // push 6, push 7, mul, ret
+    const PROGRAM: [u8; 12] = [
+        0x05, 0x00, 0x00, 0x00, 0x06, 0x05, 0x00, 0x00, 0x00, 0x07, 0x03, 0x07,
+    ];
+
+    #[test]
+    fn satisfied_circuit() {
+        let report = debug_dvm_circuit(DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42)).unwrap();
+        assert_eq!(report, None);
+    }
+
+    #[test]
+    fn wrong_result_points_at_ret() {
+        let report = debug_dvm_circuit(DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 43))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            report.source,
+            ConstraintSource::Step(StepState {
+                pc: 11,
+                opcode: BinaryCode::Ret,
+                stack: vec![42],
+            })
+        );
+        assert_eq!(report.name, "step_11_RET");
+        assert!(report
+            .to_string()
+            .ends_with("of step_11_RET is not satisfied with stack [42]"));
+    }
+
+    #[test]
+    fn locates_traced_constraints() {
+        use ark_relations::r1cs::ConstraintLayer;
+        use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Registry};
+
+        let _guard = Registry::default()
+            .with(ConstraintLayer::default())
+            .set_default();
+        let report = debug_dvm_circuit(DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 43))
+            .unwrap()
+            .unwrap();
+
+        // the constraint is reported by its trace, which names the step span
+        assert!(report.constraint.parse::<usize>().is_err());
+        assert!(report.constraint.contains("step"));
+        assert_eq!(report.name, "step_11_RET");
+    }
+
+    #[test]
+    fn counts_constraints_per_step() {
+        let counts = step_constraint_counts(DVMCircuit::<Fr>::new(PROGRAM.to_vec(), 42)).unwrap();
+        let names: Vec<&str> = counts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["step_0_PUSH", "step_5_PUSH", "step_10_MUL", "step_11_RET"]
+        );
+        // every byte runs the same constraints, so a PUSH costs as much as its five bytes
+        assert!(counts.iter().all(|&(_, count)| count > 0));
+        assert_eq!(counts[1].1, 5 * counts[2].1);
+    }
+}
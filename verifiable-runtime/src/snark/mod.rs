@@ -1,2 +1,5 @@
 mod constraints;
 pub use constraints::*;
+
+pub mod cost;
+pub mod debug;
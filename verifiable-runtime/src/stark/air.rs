@@ -0,0 +1,489 @@
+use crate::opcode::{decode, BinaryCode, Opcode};
+use crate::utils::{are_equal, not, EvaluationResult};
+use winterfell::{
+    math::{fields::f128::BaseElement, FieldElement, StarkField},
+    Air, AirContext, Assertion, ByteWriter, EvaluationFrame, ProofOptions, Serializable, TraceInfo,
+    TransitionConstraintDegree,
+};
+
+// TRACE LAYOUT
+// ================================================================================================
+
+/// Program counter, i.e. the offset of the current opcode in the program bytes.
+pub const PC_COL: usize = 0;
+
+/// One binary selector column per opcode, in the order of [SELECTORS].
+pub const SELECTOR_COL: usize = 1;
+
+/// Opcodes that have a selector column; `Invalid` can never appear in a valid trace.
+pub const SELECTORS: [BinaryCode; 9] = [
+    BinaryCode::Add,
+    BinaryCode::Sub,
+    BinaryCode::Mul,
+    BinaryCode::Div,
+    BinaryCode::Push,
+    BinaryCode::Pop,
+    BinaryCode::Ret,
+    BinaryCode::Swap,
+    BinaryCode::Stop,
+];
+
+/// Immediate parameter of the current opcode, only non-zero for `PUSH`.
+pub const PARAM_COL: usize = SELECTOR_COL + SELECTORS.len();
+
+/// Bits of the absolute quotient computed by `DIV`, least significant first.
+pub const QUOT_COL: usize = PARAM_COL + 1;
+
+/// Number of bits of the absolute quotient; `i32::MIN / 1` needs all 32 of them.
+pub const QUOT_BITS: usize = 32;
+
+/// Bits of the absolute remainder of `DIV`, least significant first.
+pub const REM_COL: usize = QUOT_COL + QUOT_BITS;
+
+/// Bits of `|b| - |rem| - 1` for the divisor `b` of `DIV`, least significant first; together
+/// with [REM_COL] they range-check the remainder to `0 <= |rem| < |b|`.
+pub const GAP_COL: usize = REM_COL + REM_BITS;
+
+/// Number of bits of the absolute remainder and of the gap to the divisor.
+pub const REM_BITS: usize = 31;
+
+/// Sign bits of the dividend and of the divisor of `DIV`, set when the operand is negative.
+pub const SIGN_COL: usize = GAP_COL + REM_BITS;
+
+/// Value returned by the last executed `RET`.
+pub const RESULT_COL: usize = SIGN_COL + 2;
+
+/// Stack columns start here, the top of the stack comes first.
+pub const STACK_COL: usize = RESULT_COL + 1;
+
+/// Returns the index of the selector column for the given opcode.
+pub fn selector_index(bin_code: BinaryCode) -> Option<usize> {
+    SELECTORS.iter().position(|&code| code == bin_code)
+}
+
+/// Returns the number of trace rows needed to execute `num_steps` opcodes; the last row holds
+/// the final state, and the length is padded to a power of two.
+pub fn trace_length(num_steps: usize) -> usize {
+    (num_steps + 1)
+        .next_power_of_two()
+        .max(TraceInfo::MIN_TRACE_LENGTH)
+}
+
+/// Returns the deepest stack reached while executing the opcodes; stack underflow is not
+/// reported here and is left to the trace builder.
+pub fn stack_depth(opcodes: &[(usize, Opcode)]) -> usize {
+    let mut depth = 0usize;
+    let mut max_depth = 1;
+    for (_, opcode) in opcodes {
+        depth = match opcode.code() {
+            BinaryCode::Push => depth + 1,
+            BinaryCode::Add
+            | BinaryCode::Sub
+            | BinaryCode::Mul
+            | BinaryCode::Div
+            | BinaryCode::Pop
+            | BinaryCode::Ret => depth.saturating_sub(1),
+            _ => depth,
+        };
+        max_depth = max_depth.max(depth);
+    }
+    max_depth
+}
+
+// PUBLIC INPUTS
+// ================================================================================================
+
+/// The program is public, so the verifier can pin every selector and parameter of the trace.
+#[derive(Clone)]
+pub struct PublicInputs {
+    pub program: Vec<u8>,
+    pub result: BaseElement,
+}
+
+impl Serializable for PublicInputs {
+    fn write_into<W: ByteWriter>(&self, target: &mut W) {
+        target.write_u64(self.program.len() as u64);
+        target.write_bytes(&self.program);
+        target.write(self.result);
+    }
+}
+
+// DVM AIR
+// ================================================================================================
+
+pub struct DVMAir {
+    context: AirContext<BaseElement>,
+    opcodes: Vec<(usize, Opcode)>,
+    stack_depth: usize,
+    uses_div: bool,
+    result: BaseElement,
+}
+
+impl DVMAir {
+    /// Returns the number of stack columns in the trace.
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+    }
+}
+
+impl Air for DVMAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // CONSTRUCTOR
+    // --------------------------------------------------------------------------------------------
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let opcodes = decode(&pub_inputs.program);
+        let stack_depth = stack_depth(&opcodes);
+        assert_eq!(STACK_COL + stack_depth, trace_info.width());
+
+        // constraint degrees depend on which opcodes the program uses: a selector column which
+        // is zero everywhere drops the terms it guards
+        let uses = |bin_code| opcodes.iter().any(|(_, opcode)| opcode.code() == bin_code);
+        let uses_div = uses(BinaryCode::Div);
+        let top_degree = if uses_div {
+            4
+        } else if uses(BinaryCode::Mul) {
+            3
+        } else {
+            2
+        };
+        let mut degrees = vec![
+            TransitionConstraintDegree::new(1),
+            TransitionConstraintDegree::new(2),
+            TransitionConstraintDegree::new(top_degree),
+        ];
+        degrees.resize_with(2 + stack_depth, || TransitionConstraintDegree::new(2));
+        if uses_div {
+            // every bit of DIV is boolean, then the divisor and the dividend are recomposed
+            degrees.resize_with(2 + stack_depth + DIV_BITS, || {
+                TransitionConstraintDegree::new(2)
+            });
+            degrees.push(TransitionConstraintDegree::new(3));
+            degrees.push(TransitionConstraintDegree::new(4));
+        }
+
+        let num_assertions = 3 + stack_depth + trace_info.length() * (SELECTORS.len() + 1);
+        DVMAir {
+            context: AirContext::new(trace_info, degrees, num_assertions, options),
+            opcodes,
+            stack_depth,
+            uses_div,
+            result: pub_inputs.result,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        _periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let current = frame.current();
+        let next = frame.next();
+
+        let flag = |bin_code| current[SELECTOR_COL + selector_index(bin_code).unwrap()];
+        let any = current[SELECTOR_COL..PARAM_COL]
+            .iter()
+            .fold(E::ZERO, |acc, &selector| acc + selector);
+        // stack slots below the traced depth are always zero
+        let slot = |i: usize| {
+            if i < self.stack_depth {
+                current[STACK_COL + i]
+            } else {
+                E::ZERO
+            }
+        };
+
+        // the program counter skips the 4 parameter bytes of PUSH and stays put on padding rows
+        result[0] = are_equal(
+            next[PC_COL],
+            current[PC_COL] + any + flag(BinaryCode::Push) * E::from(4u32),
+        );
+
+        // RET latches the top of the stack into the result column
+        let ret = flag(BinaryCode::Ret);
+        result.agg_constraint(
+            1,
+            not(ret),
+            are_equal(next[RESULT_COL], current[RESULT_COL]),
+        );
+        result.agg_constraint(1, ret, are_equal(next[RESULT_COL], slot(0)));
+
+        for i in 0..self.stack_depth {
+            let index = 2 + i;
+            let value = next[STACK_COL + i];
+
+            // rows without an opcode leave the stack untouched
+            result.agg_constraint(index, not(any), are_equal(value, slot(i)));
+            result.agg_constraint(index, flag(BinaryCode::Stop), are_equal(value, slot(i)));
+
+            // binary operations consume the two topmost slots and shift the rest up by one
+            if i == 0 {
+                let (a, b) = (slot(1), slot(0));
+                result.agg_constraint(index, flag(BinaryCode::Add), are_equal(value, a + b));
+                result.agg_constraint(index, flag(BinaryCode::Sub), are_equal(value, a - b));
+                result.agg_constraint(index, flag(BinaryCode::Mul), are_equal(value, a * b));
+                // the quotient takes the sign of a / b, as DIV rounds towards zero
+                let (a_sign, b_sign) = (sign(current[SIGN_COL]), sign(current[SIGN_COL + 1]));
+                let quotient = combine(&current[QUOT_COL..REM_COL]);
+                result.agg_constraint(
+                    index,
+                    flag(BinaryCode::Div),
+                    are_equal(value, a_sign * b_sign * quotient),
+                );
+            } else {
+                for bin_code in [
+                    BinaryCode::Add,
+                    BinaryCode::Sub,
+                    BinaryCode::Mul,
+                    BinaryCode::Div,
+                ] {
+                    result.agg_constraint(index, flag(bin_code), are_equal(value, slot(i + 1)));
+                }
+            }
+
+            // POP and RET drop the topmost slot
+            result.agg_constraint(index, flag(BinaryCode::Pop), are_equal(value, slot(i + 1)));
+            result.agg_constraint(index, flag(BinaryCode::Ret), are_equal(value, slot(i + 1)));
+
+            // PUSH shifts the stack down and puts the parameter on top
+            let pushed = if i == 0 {
+                current[PARAM_COL]
+            } else {
+                slot(i - 1)
+            };
+            result.agg_constraint(index, flag(BinaryCode::Push), are_equal(value, pushed));
+
+            // SWAP exchanges the two topmost slots
+            let swapped = match i {
+                0 => slot(1),
+                1 => slot(0),
+                _ => slot(i),
+            };
+            result.agg_constraint(index, flag(BinaryCode::Swap), are_equal(value, swapped));
+        }
+
+        if self.uses_div {
+            let operands = (slot(1), slot(0));
+            let div_result = &mut result[2 + self.stack_depth..];
+            evaluate_div(current, operands, flag(BinaryCode::Div), div_result);
+        }
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let trace_length = self.trace_length();
+        let mut assertions = vec![
+            Assertion::single(PC_COL, 0, BaseElement::ZERO),
+            Assertion::single(RESULT_COL, 0, BaseElement::ZERO),
+            Assertion::single(RESULT_COL, trace_length - 1, self.result),
+        ];
+        for i in 0..self.stack_depth {
+            assertions.push(Assertion::single(STACK_COL + i, 0, BaseElement::ZERO));
+        }
+
+        // the program is public, so every selector and parameter is pinned; steps past the end
+        // of the program are padding rows with no opcode
+        for step in 0..trace_length {
+            let opcode = self.opcodes.get(step).map(|(_, opcode)| opcode);
+            for (i, &bin_code) in SELECTORS.iter().enumerate() {
+                let selected = opcode.is_some_and(|opcode| opcode.code() == bin_code);
+                let value = if selected {
+                    BaseElement::ONE
+                } else {
+                    BaseElement::ZERO
+                };
+                assertions.push(Assertion::single(SELECTOR_COL + i, step, value));
+            }
+            let param = opcode.map_or(0, |opcode| opcode.param());
+            assertions.push(Assertion::single(PARAM_COL, step, to_element(param)));
+        }
+        assertions
+    }
+}
+
+// DIV CONSTRAINTS
+// ================================================================================================
+
+/// Number of bit columns used by `DIV`, sign bits included.
+pub const DIV_BITS: usize = RESULT_COL - QUOT_COL;
+
+/// Evaluates the constraints of `DIV` on the operands `a` and `b` at the top of the stack:
+/// `|a| = |q| * |b| + |rem|` with `|b| = |rem| + gap + 1`, where `|q|`, `|rem|` and `gap` are
+/// recomposed from their bits. The bit widths keep both sides far below the modulus, so the
+/// equation holds over the integers and the quotient is unique.
+fn evaluate_div<E: FieldElement>(current: &[E], (a, b): (E, E), flag: E, result: &mut [E]) {
+    for (i, &bit) in current[QUOT_COL..RESULT_COL].iter().enumerate() {
+        result[i] = bit * (bit - E::ONE);
+    }
+
+    let (a_sign, b_sign) = (sign(current[SIGN_COL]), sign(current[SIGN_COL + 1]));
+    let quotient = combine(&current[QUOT_COL..REM_COL]);
+    let remainder = combine(&current[REM_COL..GAP_COL]);
+    let divisor = remainder + combine(&current[GAP_COL..SIGN_COL]) + E::ONE;
+
+    // a wrong sign bit turns the divisor into a value far beyond the range of the bits
+    result.agg_constraint(DIV_BITS, flag, are_equal(b_sign * b, divisor));
+    result.agg_constraint(
+        DIV_BITS + 1,
+        flag,
+        are_equal(a, a_sign * (quotient * divisor + remainder)),
+    );
+}
+
+// HELPER FUNCTIONS
+// ================================================================================================
+
+/// Recomposes an integer from its bits, least significant first.
+fn combine<E: FieldElement>(bits: &[E]) -> E {
+    bits.iter().rev().fold(E::ZERO, |acc, &bit| acc + acc + bit)
+}
+
+/// Maps a sign bit to `1` or `-1`.
+fn sign<E: FieldElement>(bit: E) -> E {
+    E::ONE - bit - bit
+}
+
+/// Maps a DVM word into the field; negative values wrap around the modulus.
+pub fn to_element(value: i32) -> BaseElement {
+    if value < 0 {
+        -BaseElement::from(value.unsigned_abs())
+    } else {
+        BaseElement::from(value as u32)
+    }
+}
+
+/// Maps a field element back to a DVM word, or returns `None` when it is out of range.
+pub fn from_element(value: BaseElement) -> Option<i32> {
+    let value = value.as_int();
+    let negated = (-BaseElement::new(value)).as_int();
+    if value <= i32::MAX as u128 {
+        Some(value as i32)
+    } else if negated <= i32::MIN.unsigned_abs() as u128 {
+        Some(-(negated as i64) as i32)
+    } else {
+        None
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stark::{build_trace, default_proof_options, DVMProver};
+    use crate::utils::trace::failing_rows;
+    use std::panic::{self, AssertUnwindSafe};
+    use winterfell::{crypto::hashers::Blake3_256, Prover, Trace, TraceTable};
+
+    /// PUSH 7, PUSH 2, DIV, RET
+    const DIV_PROGRAM: [u8; 12] = [
+        0x05, 0x00, 0x00, 0x00, 0x07, 0x05, 0x00, 0x00, 0x00, 0x02, 0x04, 0x07,
+    ];
+
+    fn push(program: &mut Vec<u8>, value: i32) {
+        program.push(BinaryCode::Push.to());
+        program.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Proves `trace` and verifies the proof against `result`. Debug builds check the trace
+    /// against the AIR before proving and panic on a broken constraint, so a panic counts as a
+    /// rejection too.
+    fn is_accepted(program: &[u8], trace: TraceTable<BaseElement>, result: i32) -> bool {
+        let prover = DVMProver::new(default_proof_options(), program.to_vec());
+        let proof = match panic::catch_unwind(AssertUnwindSafe(|| prover.prove(trace))) {
+            Ok(Ok(proof)) => proof,
+            _ => return false,
+        };
+        let pub_inputs = PublicInputs {
+            program: program.to_vec(),
+            result: to_element(result),
+        };
+        winterfell::verify::<DVMAir, Blake3_256<BaseElement>>(proof, pub_inputs).is_ok()
+    }
+
+    fn air(program: &[u8], trace: &TraceTable<BaseElement>, result: i32) -> DVMAir {
+        let pub_inputs = PublicInputs {
+            program: program.to_vec(),
+            result: to_element(result),
+        };
+        DVMAir::new(trace.get_info(), pub_inputs, default_proof_options())
+    }
+
+    #[test]
+    fn proves_valid_programs() {
+        assert!(is_accepted(&DIV_PROGRAM, build_trace(&DIV_PROGRAM), 3));
+
+        // DIV rounds towards zero for every combination of signs
+        for (a, b) in [(-7, 2), (7, -2), (-8, -3), (i32::MIN, 1), (5, 9)] {
+            let mut program = Vec::new();
+            push(&mut program, a);
+            push(&mut program, b);
+            program.extend([BinaryCode::Div.to(), BinaryCode::Ret.to()]);
+            assert!(
+                is_accepted(&program, build_trace(&program), a / b),
+                "{} / {}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_result() {
+        assert!(!is_accepted(&DIV_PROGRAM, build_trace(&DIV_PROGRAM), 4));
+    }
+
+    #[test]
+    fn rejects_tampered_div() {
+        // 7 = 2 * 2 + 3 holds in the field, but the remainder 3 is not below the divisor 2
+        let mut trace = build_trace(&DIV_PROGRAM);
+        let last = trace.length() - 1;
+        for i in 0..QUOT_BITS {
+            trace.set(QUOT_COL + i, 2, BaseElement::from((2u32 >> i) & 1));
+        }
+        for i in 0..REM_BITS {
+            trace.set(REM_COL + i, 2, BaseElement::from((3u32 >> i) & 1));
+        }
+        trace.set(STACK_COL, 3, BaseElement::from(2u32));
+        for step in 4..=last {
+            trace.set(RESULT_COL, step, BaseElement::from(2u32));
+        }
+
+        let air = air(&DIV_PROGRAM, &trace, 2);
+        let divisor_constraint = 2 + air.stack_depth() + DIV_BITS;
+        assert_eq!(failing_rows(&air, &trace, divisor_constraint), vec![2]);
+        assert!(!is_accepted(&DIV_PROGRAM, trace, 2));
+    }
+
+    #[test]
+    fn rejects_tampered_trace() {
+        // the second PUSH puts 3 on the stack instead of its parameter
+        let mut trace = build_trace(&DIV_PROGRAM);
+        trace.set(STACK_COL, 2, BaseElement::from(3u32));
+        assert_eq!(
+            failing_rows(&air(&DIV_PROGRAM, &trace, 3), &trace, 2),
+            vec![1]
+        );
+        assert!(!is_accepted(&DIV_PROGRAM, trace, 3));
+    }
+
+    #[test]
+    fn arithmetic_follows_the_field() {
+        // PUSH i32::MAX, PUSH 1, ADD, RET
+        let mut program = Vec::new();
+        push(&mut program, i32::MAX);
+        push(&mut program, 1);
+        program.extend([BinaryCode::Add.to(), BinaryCode::Ret.to()]);
+        let trace = build_trace(&program);
+        let result = trace.get(RESULT_COL, trace.length() - 1);
+        assert_eq!(result, BaseElement::from(1u64 << 31));
+        assert_eq!(from_element(result), None);
+        assert_eq!(from_element(to_element(i32::MIN)), Some(i32::MIN));
+    }
+}
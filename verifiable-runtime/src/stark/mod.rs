@@ -0,0 +1,36 @@
+mod air;
+mod prover;
+pub use air::*;
+pub use prover::*;
+
+use crate::utils::print_trace;
+use winterfell::{
+    crypto::hashers::Blake3_256, math::fields::f128::BaseElement, FieldExtension, ProofOptions,
+    Prover,
+};
+
+/// Default proof options: 42 queries with blowup factor 8 give ~100-bit conjectured security.
+pub fn default_proof_options() -> ProofOptions {
+    ProofOptions::new(42, 8, 0, FieldExtension::None, 4, 256)
+}
+
+pub fn verify_dvm_stark(program: Vec<u8>, result: i32) {
+    // Execute the program and record the execution trace
+    let trace = build_trace(&program);
+    print_trace(&trace, 1, 0, 0..trace.width());
+
+    // Create a STARK proof, no trusted setup is needed
+    let prover = DVMProver::new(default_proof_options(), program.clone());
+    let proof = prover.prove(trace).unwrap();
+    println!(
+        "Proved DVM code with STARK proof of {} bytes",
+        proof.to_bytes().len()
+    );
+
+    let pub_inputs = PublicInputs {
+        program,
+        result: to_element(result),
+    };
+    winterfell::verify::<DVMAir, Blake3_256<BaseElement>>(proof, pub_inputs).unwrap();
+    println!("Verified STARK proof!.");
+}
@@ -0,0 +1,189 @@
+use super::air::{
+    from_element, selector_index, stack_depth, to_element, trace_length, DVMAir, PublicInputs,
+    GAP_COL, PARAM_COL, PC_COL, QUOT_COL, REM_COL, RESULT_COL, SELECTOR_COL, SIGN_COL, STACK_COL,
+};
+use crate::opcode::{decode, BinaryCode};
+use core::marker::PhantomData;
+use winterfell::{
+    crypto::{hashers::Blake3_256, ElementHasher},
+    math::{fields::f128::BaseElement, FieldElement, StarkField},
+    ProofOptions, Prover, Trace, TraceTable,
+};
+
+// TRACE BUILDER
+// ================================================================================================
+
+/// Executes the program and records one trace row per opcode, followed by padding rows which
+/// repeat the final state.
+///
+/// Stack values are field elements, so `ADD`, `SUB` and `MUL` follow the field arithmetic of
+/// the AIR instead of overflowing; `DIV` panics when an operand does not map back to a DVM word.
+pub fn build_trace(program: &[u8]) -> TraceTable<BaseElement> {
+    let opcodes = decode(program);
+    let depth = stack_depth(&opcodes);
+    let length = trace_length(opcodes.len());
+    let mut trace = TraceTable::new(STACK_COL + depth, length);
+
+    let mut stack = Vec::<BaseElement>::new();
+    let mut result = BaseElement::ZERO;
+    let mut state = vec![BaseElement::ZERO; trace.width()];
+    for step in 0..length {
+        let opcode = opcodes.get(step).map(|(_, opcode)| opcode);
+
+        // record the state before the opcode is executed
+        state.fill(BaseElement::ZERO);
+        state[PC_COL] = match opcodes.get(step) {
+            Some(&(pc, _)) => BaseElement::from(pc as u64),
+            None => BaseElement::from(program.len() as u64),
+        };
+        state[RESULT_COL] = result;
+        for (i, &value) in stack.iter().rev().enumerate() {
+            state[STACK_COL + i] = value;
+        }
+
+        if let Some(opcode) = opcode {
+            let bin_code = opcode.code();
+            let index = selector_index(bin_code)
+                .unwrap_or_else(|| panic!("Invalid opcode at step {}", step));
+            state[SELECTOR_COL + index] = BaseElement::ONE;
+            state[PARAM_COL] = to_element(opcode.param());
+
+            match bin_code {
+                BinaryCode::Add | BinaryCode::Sub | BinaryCode::Mul | BinaryCode::Div => {
+                    if stack.len() < 2 {
+                        panic!(
+                            "Can not perform {:?}, stack deep is {}",
+                            bin_code,
+                            stack.len()
+                        );
+                    }
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(match bin_code {
+                        BinaryCode::Add => a + b,
+                        BinaryCode::Sub => a - b,
+                        BinaryCode::Mul => a * b,
+                        _ => divide(a, b, &mut state),
+                    });
+                }
+                BinaryCode::Push => stack.push(to_element(opcode.param())),
+                BinaryCode::Pop => {
+                    stack.pop().unwrap();
+                }
+                BinaryCode::Ret => result = stack.pop().unwrap(),
+                BinaryCode::Swap => {
+                    if stack.len() < 2 {
+                        panic!("Can not perform SWAP, stack deep is {}", stack.len());
+                    }
+                    let n = stack.len();
+                    stack.swap(n - 1, n - 2);
+                }
+                _ => {}
+            }
+        }
+
+        trace.update_row(step, &state);
+    }
+
+    for column in QUOT_COL..RESULT_COL {
+        fill_last_row(&mut trace, column);
+    }
+    trace
+}
+
+/// Divides `a` by `b` rounding towards zero like the DVM, and records the bits proving the
+/// quotient in `state`.
+fn divide(a: BaseElement, b: BaseElement, state: &mut [BaseElement]) -> BaseElement {
+    let word = |value| {
+        from_element(value)
+            .unwrap_or_else(|| panic!("DIV operand {} is not a DVM word", value.as_int()))
+    };
+    let (a, b) = (word(a), word(b));
+    if b == 0 {
+        panic!("Divide by 0");
+    }
+    let quotient = a
+        .checked_div(b)
+        .unwrap_or_else(|| panic!("DIV overflows on {} / {}", a, b));
+    let remainder = a % b;
+    let gap = b.unsigned_abs() - remainder.unsigned_abs() - 1;
+
+    write_bits(&mut state[QUOT_COL..REM_COL], quotient.unsigned_abs());
+    write_bits(&mut state[REM_COL..GAP_COL], remainder.unsigned_abs());
+    write_bits(&mut state[GAP_COL..SIGN_COL], gap);
+    state[SIGN_COL] = BaseElement::from(u32::from(a < 0));
+    state[SIGN_COL + 1] = BaseElement::from(u32::from(b < 0));
+    to_element(quotient)
+}
+
+/// Writes the bits of `value` into `bits`, least significant first.
+fn write_bits(bits: &mut [BaseElement], value: u32) {
+    for (i, bit) in bits.iter_mut().enumerate() {
+        *bit = BaseElement::from((value >> i) & 1);
+    }
+}
+
+/// Bit columns are only constrained on rows executing `DIV`, so the last row, which is always a
+/// padding row, is free. It gets a value which raises the column polynomial to full degree:
+/// otherwise a column of equal bits would make its boolean constraint vanish, and winterfell
+/// checks constraint degrees against the declared ones.
+fn fill_last_row(trace: &mut TraceTable<BaseElement>, column: usize) {
+    // the leading coefficient of the column polynomial is proportional to sum(v_k * g^k)
+    let last = trace.length() - 1;
+    let g = BaseElement::get_root_of_unity(trace.length().trailing_zeros());
+    let sum = (0..last).fold(BaseElement::ZERO, |acc, step| {
+        acc + trace.get(column, step) * g.exp(step as u128)
+    });
+    let g_last = g.exp(last as u128);
+    let filler = (2u32..)
+        .map(BaseElement::from)
+        .find(|&filler| sum + filler * g_last != BaseElement::ZERO)
+        .unwrap();
+    trace.set(column, last, filler);
+}
+
+// DVM PROVER
+// ================================================================================================
+
+/// Proves DVM execution, committing to the trace with the hash function `H`
+pub struct DVMProver<H = Blake3_256<BaseElement>> {
+    options: ProofOptions,
+    program: Vec<u8>,
+    _hasher: PhantomData<H>,
+}
+
+impl DVMProver {
+    pub fn new(options: ProofOptions, program: Vec<u8>) -> Self {
+        Self::with_hasher(options, program)
+    }
+}
+
+impl<H: ElementHasher<BaseField = BaseElement>> DVMProver<H> {
+    /// Returns a prover using `H` for trace commitments and Fiat-Shamir; proofs must then be
+    /// verified with the same hash function
+    pub fn with_hasher(options: ProofOptions, program: Vec<u8>) -> Self {
+        Self {
+            options,
+            program,
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<H: ElementHasher<BaseField = BaseElement>> Prover for DVMProver<H> {
+    type BaseField = BaseElement;
+    type Air = DVMAir;
+    type Trace = TraceTable<BaseElement>;
+    type HashFn = H;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        PublicInputs {
+            program: self.program.clone(),
+            result: trace.get(RESULT_COL, trace.length() - 1),
+        }
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
@@ -0,0 +1,177 @@
+//! R1CS gadget replaying a [TranscriptRead] inside an arkworks circuit.
+#[cfg(doc)]
+use super::TranscriptRead;
+use super::TRANSCRIPT_DOMAIN;
+use crate::hash::AlgebraicSpongeGadget;
+use ark_ff::PrimeField;
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+
+/// Verifier transcript inside a circuit; given the same proof it derives the same challenges as
+/// a [TranscriptRead] over the native counterpart of `S`.
+pub struct TranscriptGadget<F: PrimeField, S: AlgebraicSpongeGadget<F>> {
+    cs: ConstraintSystemRef<F>,
+    sponge: S,
+}
+
+impl<F: PrimeField, S: AlgebraicSpongeGadget<F>> TranscriptGadget<F, S> {
+    pub fn new(cs: ConstraintSystemRef<F>, params: &S::Parameters) -> Self {
+        TranscriptGadget {
+            sponge: S::with_domain(cs.clone(), params, F::from(TRANSCRIPT_DOMAIN)),
+            cs,
+        }
+    }
+
+    /// Allocates the next scalar of the proof as a witness and absorbs it; `scalar` is `None`
+    /// when the circuit is synthesized without a proof.
+    pub fn read_scalar(&mut self, scalar: Option<F>) -> Result<FpVar<F>, SynthesisError> {
+        let scalar = FpVar::new_witness(self.cs.clone(), || {
+            scalar.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        self.common_scalar(&scalar)?;
+        Ok(scalar)
+    }
+
+    /// Absorbs a value already known to the circuit, such as a public input.
+    pub fn common_scalar(&mut self, scalar: &FpVar<F>) -> Result<(), SynthesisError> {
+        self.sponge.absorb(core::slice::from_ref(scalar))
+    }
+
+    pub fn squeeze_challenge(&mut self) -> Result<FpVar<F>, SynthesisError> {
+        Ok(self.sponge.squeeze(1)?.remove(0))
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::rescue::{constraints::RescueGadget, Rescue, RescueParameters};
+    use crate::transcript::{Transcript, TranscriptRead, TranscriptWrite};
+    use ark_bls12_377::Fr;
+    use ark_r1cs_std::R1CSVar;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    type Writer = TranscriptWrite<Fr, Rescue<Fr>, Vec<u8>>;
+    type Reader<'a> = TranscriptRead<Fr, Rescue<Fr>, &'a [u8]>;
+
+    fn params() -> RescueParameters<Fr> {
+        RescueParameters::new(3, 1, 128)
+    }
+
+    /// Writes a toy proof of two rounds, returning it with the challenges of each round.
+    fn prove(messages: &[u64]) -> (Vec<u8>, Vec<Fr>) {
+        let mut transcript = Writer::init(&params(), vec![]);
+        transcript.common_scalar(Fr::from(42u64));
+        let mut challenges = vec![];
+        for round in messages.chunks(2) {
+            for &message in round {
+                transcript.write_scalar(Fr::from(message)).unwrap();
+            }
+            challenges.push(transcript.squeeze_challenge());
+        }
+        (transcript.finalize(), challenges)
+    }
+
+    fn verify(proof: &[u8], rounds: usize) -> std::io::Result<Vec<Fr>> {
+        let mut transcript = Reader::init(&params(), proof);
+        transcript.common_scalar(Fr::from(42u64));
+        (0..rounds)
+            .map(|_| {
+                transcript.read_scalar()?;
+                transcript.read_scalar()?;
+                Ok(transcript.squeeze_challenge())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reader_replays_writer() {
+        let (proof, challenges) = prove(&[1, 2, 3, 4]);
+        assert_eq!(proof.len(), 4 * 32);
+        assert_eq!(verify(&proof, 2).unwrap(), challenges);
+        assert_ne!(challenges[0], challenges[1]);
+
+        // a different message changes every later challenge
+        let (_, other) = prove(&[1, 2, 3, 5]);
+        assert_eq!(other[0], challenges[0]);
+        assert_ne!(other[1], challenges[1]);
+    }
+
+    #[test]
+    fn reader_rejects_malformed_proofs() {
+        let (mut proof, _) = prove(&[1, 2]);
+        assert!(verify(&proof[..40], 1).is_err());
+
+        // the modulus itself is a non-canonical encoding of zero
+        let mut modulus = <Fr as crate::hash::rescue::RescueField>::modulus().to_bytes_le();
+        modulus.resize(32, 0);
+        proof[..32].copy_from_slice(&modulus);
+        let err = verify(&proof, 1).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn gadget_matches_native_challenges() {
+        let (proof, challenges) = prove(&[1, 2, 3, 4]);
+        let mut reader = Reader::init(&params(), &proof[..]);
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut transcript = TranscriptGadget::<Fr, RescueGadget<Fr>>::new(cs.clone(), &params());
+        let public = FpVar::new_input(cs.clone(), || Ok(Fr::from(42u64))).unwrap();
+        transcript.common_scalar(&public).unwrap();
+        for &expected in challenges.iter() {
+            for _ in 0..2 {
+                let message = reader.read_scalar().unwrap();
+                let var = transcript.read_scalar(Some(message)).unwrap();
+                assert_eq!(var.value().unwrap(), message);
+            }
+            let challenge = transcript.squeeze_challenge().unwrap();
+            assert_eq!(challenge.value().unwrap(), expected);
+        }
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[cfg(feature = "stark")]
+    #[test]
+    fn rescue128_gadget_matches_native_challenges() {
+        use crate::transcript::{Rescue128Read, Rescue128Write};
+        use crate::utils::rescue::constraints::Rescue128Gadget;
+        use winterfell::math::{fields::f128::BaseElement, StarkField};
+
+        // STARK field elements are emulated inside Fr, where they keep their canonical value
+        let emulated = |x: BaseElement| Fr::from(x.as_int());
+
+        let mut writer = Rescue128Write::init(&(), vec![]);
+        writer.common_scalar(BaseElement::new(42));
+        let mut challenges = vec![];
+        for round in [[1, 2], [3, u128::MAX]] {
+            for message in round {
+                writer.write_scalar(BaseElement::new(message)).unwrap();
+            }
+            challenges.push(writer.squeeze_challenge());
+        }
+        let proof = writer.finalize();
+
+        let mut reader = Rescue128Read::init(&(), &proof[..]);
+        reader.common_scalar(BaseElement::new(42));
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut transcript = TranscriptGadget::<Fr, Rescue128Gadget<Fr>>::new(cs.clone(), &());
+        let public = FpVar::new_input(cs.clone(), || Ok(Fr::from(42u64))).unwrap();
+        transcript.common_scalar(&public).unwrap();
+        for &expected in challenges.iter() {
+            for _ in 0..2 {
+                let message = reader.read_scalar().unwrap();
+                let var = transcript.read_scalar(Some(emulated(message))).unwrap();
+                assert_eq!(var.value().unwrap(), emulated(message));
+            }
+            let challenge = reader.squeeze_challenge();
+            assert_eq!(challenge, expected);
+            let var = transcript.squeeze_challenge().unwrap();
+            assert_eq!(var.value().unwrap(), emulated(challenge));
+        }
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
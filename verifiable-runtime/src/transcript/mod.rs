@@ -0,0 +1,168 @@
+//! Fiat-Shamir transcripts whose challenges are squeezed from an algebraic sponge.
+//!
+//! They play the role of `Blake2bWrite`/`Blake2bRead` in halo2: the prover writes its messages
+//! through a [TranscriptWrite] into the proof, and the verifier replays them from the proof with
+//! a [TranscriptRead]. Both absorb every message into the same sponge, so they derive the same
+//! challenges. Since the sponge is algebraic, [constraints::TranscriptGadget] recomputes the
+//! challenges inside a circuit for a fraction of the cost of a bit-oriented hash, which is what
+//! recursive verification of DVM proofs needs.
+//!
+//! Field elements are written as fixed-length little-endian integers; the reader rejects
+//! encodings that are not canonical, so a proof has exactly one byte representation.
+#![deny(unsafe_code)]
+
+use crate::hash::{rescue::RescueField, AlgebraicSponge};
+use core::marker::PhantomData;
+use num_bigint::BigUint;
+use std::io::{self, Read, Write};
+
+#[cfg(feature = "stark")]
+use crate::utils::rescue::Rescue128;
+#[cfg(feature = "stark")]
+use winterfell::math::fields::f128::BaseElement;
+
+pub mod constraints;
+
+/// Domain of the transcript sponge, distinct from the byte hashing and program commitment
+/// domains
+pub const TRANSCRIPT_DOMAIN: u64 = 3;
+
+/// Transcript of a proof over the STARK field, with challenges from [Rescue128]
+#[cfg(feature = "stark")]
+pub type Rescue128Write<W> = TranscriptWrite<BaseElement, Rescue128, W>;
+
+/// Verifier counterpart of [Rescue128Write]
+#[cfg(feature = "stark")]
+pub type Rescue128Read<R> = TranscriptRead<BaseElement, Rescue128, R>;
+
+// TRANSCRIPT
+// ================================================================================================
+
+/// Operations shared by the prover and verifier sides of a transcript
+pub trait Transcript<F> {
+    /// Returns a challenge bound to everything absorbed so far.
+    fn squeeze_challenge(&mut self) -> F;
+
+    /// Absorbs a value both parties know without writing it into the proof.
+    fn common_scalar(&mut self, scalar: F);
+}
+
+/// Number of bytes of an encoded element of `F`
+pub fn encoded_len<F: RescueField>() -> usize {
+    (F::modulus().bits() as usize).div_ceil(8)
+}
+
+// WRITER
+// ================================================================================================
+
+/// Prover side of a transcript; messages are absorbed and written to `W`.
+pub struct TranscriptWrite<F, S, W> {
+    sponge: S,
+    writer: W,
+    _field: PhantomData<F>,
+}
+
+impl<F: RescueField, S: AlgebraicSponge<F>, W: Write> TranscriptWrite<F, S, W> {
+    /// Starts a transcript writing the proof into `writer`.
+    pub fn init(params: &S::Parameters, writer: W) -> Self {
+        TranscriptWrite {
+            sponge: S::with_domain(params, F::from_biguint(&TRANSCRIPT_DOMAIN.into())),
+            writer,
+            _field: PhantomData,
+        }
+    }
+
+    /// Absorbs `scalar` and appends it to the proof.
+    pub fn write_scalar(&mut self, scalar: F) -> io::Result<()> {
+        self.common_scalar(scalar);
+        let mut bytes = scalar.to_biguint().to_bytes_le();
+        bytes.resize(encoded_len::<F>(), 0);
+        self.writer.write_all(&bytes)
+    }
+
+    /// Returns the writer holding the proof.
+    pub fn finalize(self) -> W {
+        self.writer
+    }
+}
+
+impl<F: RescueField, S: AlgebraicSponge<F>, W: Write> Transcript<F> for TranscriptWrite<F, S, W> {
+    fn squeeze_challenge(&mut self) -> F {
+        self.sponge.squeeze(1)[0]
+    }
+
+    fn common_scalar(&mut self, scalar: F) {
+        self.sponge.absorb(&[scalar]);
+    }
+}
+
+// READER
+// ================================================================================================
+
+/// Verifier side of a transcript; messages are read from `R` and absorbed.
+pub struct TranscriptRead<F, S, R> {
+    sponge: S,
+    reader: R,
+    _field: PhantomData<F>,
+}
+
+impl<F: RescueField, S: AlgebraicSponge<F>, R: Read> TranscriptRead<F, S, R> {
+    /// Starts a transcript reading the proof from `reader`.
+    pub fn init(params: &S::Parameters, reader: R) -> Self {
+        TranscriptRead {
+            sponge: S::with_domain(params, F::from_biguint(&TRANSCRIPT_DOMAIN.into())),
+            reader,
+            _field: PhantomData,
+        }
+    }
+
+    /// Reads the next scalar of the proof and absorbs it.
+    pub fn read_scalar(&mut self) -> io::Result<F> {
+        let mut bytes = vec![0u8; encoded_len::<F>()];
+        self.reader.read_exact(&mut bytes)?;
+        let value = BigUint::from_bytes_le(&bytes);
+        if value >= F::modulus() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "scalar encoding is not canonical",
+            ));
+        }
+        let scalar = F::from_biguint(&value);
+        self.common_scalar(scalar);
+        Ok(scalar)
+    }
+}
+
+impl<F: RescueField, S: AlgebraicSponge<F>, R: Read> Transcript<F> for TranscriptRead<F, S, R> {
+    fn squeeze_challenge(&mut self) -> F {
+        self.sponge.squeeze(1)[0]
+    }
+
+    fn common_scalar(&mut self, scalar: F) {
+        self.sponge.absorb(&[scalar]);
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(all(test, feature = "stark"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rescue128_transcript() {
+        let mut writer = Rescue128Write::init(&(), vec![]);
+        writer.write_scalar(BaseElement::new(7)).unwrap();
+        let challenge = writer.squeeze_challenge();
+        writer.write_scalar(-BaseElement::new(1)).unwrap();
+        let proof = writer.finalize();
+        assert_eq!(proof.len(), 32);
+
+        let mut reader = Rescue128Read::init(&(), &proof[..]);
+        assert_eq!(reader.read_scalar().unwrap(), BaseElement::new(7));
+        assert_eq!(reader.squeeze_challenge(), challenge);
+        assert_eq!(reader.read_scalar().unwrap(), -BaseElement::new(1));
+        assert!(reader.read_scalar().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! Sparse Merkle tree over [Rescue128::merge].
+//!
+//! Leaves are addressed by a `depth`-bit index. Only non-empty nodes are stored; every other
+//! node is the root of an empty subtree, whose hash depends only on its height. The empty leaf
+//! is `Hash::default()`, so an absent key can be proven with the same path as a present one.
+//! The [air] submodule proves a [MerkleProof] in a STARK.
+use super::rescue::{Hash, Rescue128};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use winterfell::{
+    crypto::Hasher, ByteReader, ByteWriter, Deserializable, DeserializationError, Serializable,
+};
+
+pub mod air;
+
+/// Deepest supported tree, so that indexes fit into a `u64`
+pub const MAX_DEPTH: usize = 64;
+
+// ERRORS
+// ================================================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    /// Depth must be between 1 and [MAX_DEPTH]
+    InvalidDepth(usize),
+    /// Index does not fit into the tree depth
+    IndexOutOfRange(u64),
+    /// The empty leaf can not be inserted, as it would be indistinguishable from an absent one
+    EmptyValue(u64),
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDepth(depth) => {
+                write!(f, "tree depth {} is not in 1..={}", depth, MAX_DEPTH)
+            }
+            Self::IndexOutOfRange(index) => write!(f, "index {} is out of range", index),
+            Self::EmptyValue(index) => write!(f, "can not insert the empty leaf at {}", index),
+        }
+    }
+}
+
+impl std::error::Error for MerkleError {}
+
+// SPARSE MERKLE TREE
+// ================================================================================================
+
+#[derive(Clone, Debug)]
+pub struct SparseMerkleTree {
+    depth: usize,
+    /// Non-empty nodes keyed by (height, index); leaves have height 0
+    nodes: BTreeMap<(usize, u64), Hash>,
+    /// `empty[h]` is the root of an empty subtree of height `h`
+    empty: Vec<Hash>,
+}
+
+impl SparseMerkleTree {
+    /// Returns an empty tree with `2^depth` leaves.
+    pub fn new(depth: usize) -> Result<Self, MerkleError> {
+        if depth == 0 || depth > MAX_DEPTH {
+            return Err(MerkleError::InvalidDepth(depth));
+        }
+        let mut empty = vec![Hash::default()];
+        for height in 0..depth {
+            empty.push(Rescue128::merge(&[empty[height], empty[height]]));
+        }
+        Ok(SparseMerkleTree {
+            depth,
+            nodes: BTreeMap::new(),
+            empty,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> Hash {
+        self.node(self.depth, 0)
+    }
+
+    /// Returns the leaf at `index`, or `None` when it is empty.
+    pub fn get(&self, index: u64) -> Option<Hash> {
+        self.nodes.get(&(0, index)).copied()
+    }
+
+    /// Sets the leaf at `index` and returns its previous value.
+    pub fn insert(&mut self, index: u64, value: Hash) -> Result<Option<Hash>, MerkleError> {
+        let previous = self.get(index);
+        self.insert_batch([(index, value)])?;
+        Ok(previous)
+    }
+
+    /// Sets all `leaves` and then recomputes every affected node once. Either all leaves are
+    /// inserted or, on error, none of them.
+    pub fn insert_batch<I>(&mut self, leaves: I) -> Result<(), MerkleError>
+    where
+        I: IntoIterator<Item = (u64, Hash)>,
+    {
+        let leaves: Vec<(u64, Hash)> = leaves.into_iter().collect();
+        for &(index, value) in leaves.iter() {
+            self.check_index(index)?;
+            if value == self.empty[0] {
+                return Err(MerkleError::EmptyValue(index));
+            }
+        }
+        self.update_leaves(leaves);
+        Ok(())
+    }
+
+    /// Clears the leaf at `index` and returns its previous value.
+    pub fn remove(&mut self, index: u64) -> Result<Option<Hash>, MerkleError> {
+        self.check_index(index)?;
+        let previous = self.get(index);
+        self.update_leaves(vec![(index, self.empty[0])]);
+        Ok(previous)
+    }
+
+    /// Returns the authentication path of the leaf at `index`; it proves membership of the
+    /// leaf when it is set and non-membership when it is empty.
+    pub fn prove(&self, index: u64) -> Result<MerkleProof, MerkleError> {
+        self.check_index(index)?;
+        let path = (0..self.depth)
+            .map(|height| self.node(height, (index >> height) ^ 1))
+            .collect();
+        Ok(MerkleProof { index, path })
+    }
+
+    // HELPER FUNCTIONS
+    // --------------------------------------------------------------------------------------------
+
+    fn check_index(&self, index: u64) -> Result<(), MerkleError> {
+        if self.depth < MAX_DEPTH && index >> self.depth != 0 {
+            return Err(MerkleError::IndexOutOfRange(index));
+        }
+        Ok(())
+    }
+
+    fn node(&self, height: usize, index: u64) -> Hash {
+        self.nodes
+            .get(&(height, index))
+            .copied()
+            .unwrap_or(self.empty[height])
+    }
+
+    fn set_node(&mut self, height: usize, index: u64, value: Hash) {
+        if value == self.empty[height] {
+            self.nodes.remove(&(height, index));
+        } else {
+            self.nodes.insert((height, index), value);
+        }
+    }
+
+    fn update_leaves(&mut self, leaves: Vec<(u64, Hash)>) {
+        let mut dirty = BTreeSet::new();
+        for (index, value) in leaves {
+            self.set_node(0, index, value);
+            dirty.insert(index >> 1);
+        }
+
+        for height in 1..=self.depth {
+            for &index in dirty.iter() {
+                let left = self.node(height - 1, index << 1);
+                let right = self.node(height - 1, (index << 1) | 1);
+                self.set_node(height, index, Rescue128::merge(&[left, right]));
+            }
+            dirty = dirty.iter().map(|index| index >> 1).collect();
+        }
+    }
+}
+
+// MERKLE PROOF
+// ================================================================================================
+
+/// Authentication path of one leaf, siblings ordered from the leaf up to the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    index: u64,
+    path: Vec<Hash>,
+}
+
+impl MerkleProof {
+    pub fn new(index: u64, path: Vec<Hash>) -> Self {
+        MerkleProof { index, path }
+    }
+
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn path(&self) -> &[Hash] {
+        &self.path
+    }
+
+    /// Returns the root of a tree holding `leaf` at the proven index.
+    pub fn compute_root(&self, leaf: Hash) -> Hash {
+        self.path
+            .iter()
+            .enumerate()
+            .fold(leaf, |node, (height, &sibling)| {
+                if (self.index >> height) & 1 == 0 {
+                    Rescue128::merge(&[node, sibling])
+                } else {
+                    Rescue128::merge(&[sibling, node])
+                }
+            })
+    }
+
+    /// Returns true if `value` is set at the proven index of the tree with `root`.
+    pub fn verify_membership(&self, root: Hash, value: Hash) -> bool {
+        value != Hash::default() && self.compute_root(value) == root
+    }
+
+    /// Returns true if the proven index is empty in the tree with `root`.
+    pub fn verify_non_membership(&self, root: Hash) -> bool {
+        self.compute_root(Hash::default()) == root
+    }
+}
+
+impl Serializable for MerkleProof {
+    fn write_into<W: ByteWriter>(&self, target: &mut W) {
+        target.write_u64(self.index);
+        target.write_u8(self.path.len() as u8);
+        for node in self.path.iter() {
+            node.write_into(target);
+        }
+    }
+}
+
+impl Deserializable for MerkleProof {
+    fn read_from<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        let index = source.read_u64()?;
+        let depth = source.read_u8()? as usize;
+        if depth == 0 || depth > MAX_DEPTH {
+            return Err(DeserializationError::InvalidValue(format!(
+                "invalid Merkle path length {}",
+                depth
+            )));
+        }
+        let path = Hash::read_batch_from(source, depth)?;
+        Ok(MerkleProof { index, path })
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winterfell::math::{fields::f128::BaseElement, FieldElement};
+
+    fn leaf(value: u128) -> Hash {
+        Rescue128::hash(&value.to_le_bytes())
+    }
+
+    #[test]
+    fn empty_tree_root() {
+        let tree = SparseMerkleTree::new(3).unwrap();
+        let mut root = Hash::default();
+        for _ in 0..3 {
+            root = Rescue128::merge(&[root, root]);
+        }
+        assert_eq!(tree.root(), root);
+    }
+
+    #[test]
+    fn matches_dense_tree() {
+        let mut tree = SparseMerkleTree::new(2).unwrap();
+        tree.insert_batch((0..4).map(|i| (i, leaf(i as u128))))
+            .unwrap();
+
+        let left = Rescue128::merge(&[leaf(0), leaf(1)]);
+        let right = Rescue128::merge(&[leaf(2), leaf(3)]);
+        assert_eq!(tree.root(), Rescue128::merge(&[left, right]));
+    }
+
+    #[test]
+    fn batch_matches_incremental_updates() {
+        let leaves: Vec<(u64, Hash)> = [3u64, 17, 1 << 40, 5, 17]
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| (index, leaf(i as u128)))
+            .collect();
+
+        let mut batch = SparseMerkleTree::new(48).unwrap();
+        batch.insert_batch(leaves.clone()).unwrap();
+
+        let mut incremental = SparseMerkleTree::new(48).unwrap();
+        for &(index, value) in leaves.iter() {
+            incremental.insert(index, value).unwrap();
+        }
+        assert_eq!(batch.root(), incremental.root());
+        assert_eq!(batch.get(17), Some(leaf(4)));
+    }
+
+    #[test]
+    fn remove_restores_root() {
+        let mut tree = SparseMerkleTree::new(16).unwrap();
+        tree.insert(7, leaf(1)).unwrap();
+        let root = tree.root();
+
+        assert_eq!(tree.insert(9, leaf(2)).unwrap(), None);
+        assert_eq!(tree.insert(9, leaf(3)).unwrap(), Some(leaf(2)));
+        assert_eq!(tree.remove(9).unwrap(), Some(leaf(3)));
+        assert_eq!(tree.root(), root);
+        // only the remaining leaf and its ancestors are stored
+        assert_eq!(tree.nodes.len(), 17);
+    }
+
+    #[test]
+    fn membership_proofs() {
+        let mut tree = SparseMerkleTree::new(20).unwrap();
+        tree.insert_batch([(1, leaf(1)), (1000, leaf(2))]).unwrap();
+        let root = tree.root();
+
+        let proof = tree.prove(1000).unwrap();
+        assert!(proof.verify_membership(root, leaf(2)));
+        assert!(!proof.verify_membership(root, leaf(1)));
+        assert!(!proof.verify_non_membership(root));
+
+        let proof = tree.prove(999).unwrap();
+        assert!(proof.verify_non_membership(root));
+        assert!(!proof.verify_membership(root, leaf(2)));
+
+        let bytes = proof.to_bytes();
+        assert_eq!(MerkleProof::read_from_bytes(&bytes).unwrap(), proof);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert_eq!(
+            SparseMerkleTree::new(0).unwrap_err(),
+            MerkleError::InvalidDepth(0)
+        );
+        let mut tree = SparseMerkleTree::new(4).unwrap();
+        assert_eq!(
+            tree.insert(16, leaf(1)).unwrap_err(),
+            MerkleError::IndexOutOfRange(16)
+        );
+        let empty = Hash::new(BaseElement::ZERO, BaseElement::ZERO);
+        assert_eq!(
+            tree.insert_batch([(1, leaf(1)), (2, empty)]).unwrap_err(),
+            MerkleError::EmptyValue(2)
+        );
+        // a failed batch leaves the tree untouched
+        assert_eq!(tree.get(1), None);
+
+        let mut full = SparseMerkleTree::new(MAX_DEPTH).unwrap();
+        full.insert(u64::MAX, leaf(1)).unwrap();
+        assert!(full
+            .prove(u64::MAX)
+            .unwrap()
+            .verify_membership(full.root(), leaf(1)));
+    }
+}
@@ -0,0 +1,337 @@
+//! AIR proving a [MerkleProof] of the Rescue-based sparse Merkle tree.
+//!
+//! Each level of the path takes one cycle of [CYCLE_LENGTH] rows: the first 7 rows apply the
+//! rounds of `Rescue128::merge`, and the last row moves the digest into the left or right half
+//! of the next hash state, depending on the index bit of the next level.
+use super::MerkleProof;
+use crate::utils::{
+    are_equal, is_binary, is_zero, not,
+    rescue::{self, Hash, CYCLE_LENGTH, LENGTH_IDX, NUM_ROUNDS, RATE_WIDTH, STATE_WIDTH},
+    EvaluationResult,
+};
+use core::marker::PhantomData;
+use winterfell::{
+    crypto::{hashers::Blake3_256, ElementHasher},
+    math::{fields::f128::BaseElement, FieldElement},
+    Air, AirContext, Assertion, ByteWriter, EvaluationFrame, ProofOptions, Prover, Serializable,
+    TraceInfo, TraceTable, TransitionConstraintDegree,
+};
+
+/// Column holding the index bit of the level being hashed
+pub const BIT_COL: usize = STATE_WIDTH;
+
+/// Merge absorbs two digests, so its length capacity element holds 4.
+const MERGE_LENGTH: u64 = RATE_WIDTH as u64;
+
+/// Rounds are applied on every row of a cycle but the last
+const CYCLE_MASK: [BaseElement; CYCLE_LENGTH] = [
+    BaseElement::ONE,
+    BaseElement::ONE,
+    BaseElement::ONE,
+    BaseElement::ONE,
+    BaseElement::ONE,
+    BaseElement::ONE,
+    BaseElement::ONE,
+    BaseElement::ZERO,
+];
+
+// PUBLIC INPUTS
+// ================================================================================================
+
+/// A membership proof has a non-empty `value`; a non-membership proof uses `Hash::default()`.
+#[derive(Clone)]
+pub struct MerklePublicInputs {
+    pub root: Hash,
+    pub value: Hash,
+    pub index: u64,
+    pub depth: usize,
+}
+
+impl Serializable for MerklePublicInputs {
+    fn write_into<W: ByteWriter>(&self, target: &mut W) {
+        self.root.write_into(target);
+        self.value.write_into(target);
+        target.write_u64(self.index);
+        target.write_u8(self.depth as u8);
+    }
+}
+
+// MERKLE AIR
+// ================================================================================================
+
+pub struct MerkleAir {
+    context: AirContext<BaseElement>,
+    pub_inputs: MerklePublicInputs,
+}
+
+impl Air for MerkleAir {
+    type BaseField = BaseElement;
+    type PublicInputs = MerklePublicInputs;
+
+    // CONSTRUCTOR
+    // --------------------------------------------------------------------------------------------
+    fn new(trace_info: TraceInfo, pub_inputs: MerklePublicInputs, options: ProofOptions) -> Self {
+        assert_eq!(STATE_WIDTH + 1, trace_info.width());
+        assert!(pub_inputs.depth * CYCLE_LENGTH <= trace_info.length());
+
+        let mut degrees =
+            vec![TransitionConstraintDegree::with_cycles(5, vec![CYCLE_LENGTH]); STATE_WIDTH];
+        degrees.push(TransitionConstraintDegree::new(2));
+
+        // leaf value, capacity at the first row, index bits and the root
+        let num_assertions = 2 + 2 + pub_inputs.depth + 2;
+        MerkleAir {
+            context: AirContext::new(trace_info, degrees, num_assertions, options),
+            pub_inputs,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let current = frame.current();
+        let next = frame.next();
+
+        // split periodic values into the round mask and Rescue round constants
+        let round_flag = periodic_values[0];
+        let ark = &periodic_values[1..];
+
+        // when round_flag = 1, constraints for Rescue round are enforced
+        rescue::enforce_round(
+            result,
+            &current[..STATE_WIDTH],
+            &next[..STATE_WIDTH],
+            ark,
+            round_flag,
+        );
+
+        // when round_flag = 0, the digest must be placed into registers [0, 1] when the next
+        // index bit is 0, and into registers [2, 3] when it is 1; the other half is the sibling
+        let init_flag = not(round_flag);
+        let bit = next[BIT_COL];
+        let not_bit = not(bit);
+        result.agg_constraint(0, init_flag, not_bit * are_equal(current[0], next[0]));
+        result.agg_constraint(1, init_flag, not_bit * are_equal(current[1], next[1]));
+        result.agg_constraint(2, init_flag, bit * are_equal(current[0], next[2]));
+        result.agg_constraint(3, init_flag, bit * are_equal(current[1], next[3]));
+
+        // the capacity of the next merge is reset to the merge length
+        result.agg_constraint(4, init_flag, is_zero(next[4]));
+        result.agg_constraint(
+            LENGTH_IDX,
+            init_flag,
+            are_equal(next[LENGTH_IDX], E::from(MERGE_LENGTH)),
+        );
+
+        // index bits must be binary
+        result[BIT_COL] = is_binary(current[BIT_COL]);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let [v0, v1] = self.pub_inputs.value.to_elements();
+        let [r0, r1] = self.pub_inputs.root.to_elements();
+        let depth = self.pub_inputs.depth;
+
+        // the leaf goes into the half of the first state selected by the lowest index bit
+        let leaf_col = if self.pub_inputs.index & 1 == 0 { 0 } else { 2 };
+        let mut assertions = vec![
+            Assertion::single(leaf_col, 0, v0),
+            Assertion::single(leaf_col + 1, 0, v1),
+            Assertion::single(4, 0, BaseElement::ZERO),
+            Assertion::single(LENGTH_IDX, 0, BaseElement::from(MERGE_LENGTH)),
+        ];
+        for height in 0..depth {
+            let bit = (self.pub_inputs.index >> height) & 1;
+            assertions.push(Assertion::single(
+                BIT_COL,
+                height * CYCLE_LENGTH,
+                BaseElement::from(bit),
+            ));
+        }
+        let last_step = depth * CYCLE_LENGTH - 1;
+        assertions.push(Assertion::single(0, last_step, r0));
+        assertions.push(Assertion::single(1, last_step, r1));
+        assertions
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut result = vec![CYCLE_MASK.to_vec()];
+        result.append(&mut rescue::get_round_constants());
+        result
+    }
+}
+
+// TRACE BUILDER
+// ================================================================================================
+
+/// Records the hashing of `value` along the path of `proof`. Cycles beyond the path depth,
+/// added to reach a power of two length, keep hashing with zero siblings and are not asserted.
+pub fn build_trace(proof: &MerkleProof, value: Hash) -> TraceTable<BaseElement> {
+    let depth = proof.path().len();
+    let length = (depth * CYCLE_LENGTH).next_power_of_two();
+    let mut trace = TraceTable::new(STATE_WIDTH + 1, length);
+
+    let mut node = value;
+    let mut row = vec![BaseElement::ZERO; STATE_WIDTH + 1];
+    for cycle in 0..length / CYCLE_LENGTH {
+        let sibling = proof.path().get(cycle).copied().unwrap_or_default();
+        let bit = if cycle < depth {
+            (proof.index() >> cycle) & 1
+        } else {
+            0
+        };
+        let (left, right) = if bit == 0 {
+            (node, sibling)
+        } else {
+            (sibling, node)
+        };
+
+        let [l0, l1] = left.to_elements();
+        let [r0, r1] = right.to_elements();
+        let mut state = [
+            l0,
+            l1,
+            r0,
+            r1,
+            BaseElement::ZERO,
+            BaseElement::from(MERGE_LENGTH),
+        ];
+        for step in 0..CYCLE_LENGTH {
+            row[..STATE_WIDTH].copy_from_slice(&state);
+            row[BIT_COL] = BaseElement::from(bit);
+            trace.update_row(cycle * CYCLE_LENGTH + step, &row);
+            if step < NUM_ROUNDS {
+                rescue::apply_round(&mut state, step);
+            }
+        }
+        node = Hash::new(state[0], state[1]);
+    }
+    trace
+}
+
+// MERKLE PROVER
+// ================================================================================================
+
+/// Proves a Merkle path, committing to the trace with the hash function `H`
+pub struct MerkleProver<H = Blake3_256<BaseElement>> {
+    options: ProofOptions,
+    pub_inputs: MerklePublicInputs,
+    _hasher: PhantomData<H>,
+}
+
+impl MerkleProver {
+    pub fn new(options: ProofOptions, pub_inputs: MerklePublicInputs) -> Self {
+        Self::with_hasher(options, pub_inputs)
+    }
+}
+
+impl<H: ElementHasher<BaseField = BaseElement>> MerkleProver<H> {
+    /// Returns a prover using `H` for trace commitments and Fiat-Shamir; proofs must then be
+    /// verified with the same hash function
+    pub fn with_hasher(options: ProofOptions, pub_inputs: MerklePublicInputs) -> Self {
+        Self {
+            options,
+            pub_inputs,
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<H: ElementHasher<BaseField = BaseElement>> Prover for MerkleProver<H> {
+    type BaseField = BaseElement;
+    type Air = MerkleAir;
+    type Trace = TraceTable<BaseElement>;
+    type HashFn = H;
+
+    fn get_pub_inputs(&self, _trace: &Self::Trace) -> MerklePublicInputs {
+        self.pub_inputs.clone()
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stark::default_proof_options;
+    use crate::utils::merkle::SparseMerkleTree;
+    use winterfell::crypto::Hasher;
+
+    /// Proves the path of `index` in `tree`, then verifies it against `value` and `root`
+    fn prove_and_verify(tree: &SparseMerkleTree, index: u64, value: Hash, root: Hash) -> bool {
+        let pub_inputs = MerklePublicInputs {
+            root: tree.root(),
+            value: tree.get(index).unwrap_or_default(),
+            index,
+            depth: tree.depth(),
+        };
+        let trace = build_trace(&tree.prove(index).unwrap(), pub_inputs.value);
+        let prover = MerkleProver::new(default_proof_options(), pub_inputs.clone());
+        let proof = prover.prove(trace).unwrap();
+
+        let claimed = MerklePublicInputs {
+            root,
+            value,
+            ..pub_inputs
+        };
+        winterfell::verify::<MerkleAir, Blake3_256<BaseElement>>(proof, claimed).is_ok()
+    }
+
+    fn tree() -> SparseMerkleTree {
+        let mut tree = SparseMerkleTree::new(5).unwrap();
+        tree.insert_batch([
+            (6, rescue::Rescue128::hash(b"a")),
+            (13, rescue::Rescue128::hash(b"b")),
+        ])
+        .unwrap();
+        tree
+    }
+
+    #[test]
+    fn proves_membership() {
+        let tree = tree();
+        assert!(prove_and_verify(
+            &tree,
+            13,
+            tree.get(13).unwrap(),
+            tree.root()
+        ));
+    }
+
+    #[test]
+    fn proves_non_membership() {
+        let tree = tree();
+        assert!(prove_and_verify(&tree, 14, Hash::default(), tree.root()));
+    }
+
+    #[test]
+    fn rejects_wrong_claims() {
+        let tree = tree();
+        let root = rescue::Rescue128::hash(b"root");
+        assert!(!prove_and_verify(&tree, 6, tree.get(6).unwrap(), root));
+        assert!(!prove_and_verify(
+            &tree,
+            6,
+            tree.get(13).unwrap(),
+            tree.root()
+        ));
+        assert!(!prove_and_verify(
+            &tree,
+            7,
+            tree.get(6).unwrap(),
+            tree.root()
+        ));
+    }
+}
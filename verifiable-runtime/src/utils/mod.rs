@@ -8,7 +8,9 @@ use winterfell::{
     Trace, TraceTable,
 };
 
+pub mod merkle;
 pub mod rescue;
+pub mod trace;
 
 // CONSTRAINT EVALUATION HELPERS
 // ================================================================================================
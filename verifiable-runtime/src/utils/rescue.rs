@@ -2,14 +2,21 @@
 //
 // This work is licensed under the terms of the MIT license.
 // For a copy, see <https://opensource.org/licenses/MIT>.
+#![deny(unsafe_code)]
+
+use crate::hash::{
+    rescue::{self, Rescue, RescueParameters},
+    AlgebraicSponge,
+};
 use crate::utils::{are_equal, EvaluationResult};
-use core::slice;
 use winterfell::{
-    crypto::{Digest, Hasher},
+    crypto::{Digest, ElementHasher, Hasher},
     math::{fields::f128::BaseElement, FieldElement},
     ByteReader, ByteWriter, Deserializable, DeserializationError, Serializable,
 };
 
+pub mod constraints;
+
 /// Function state is set to 6 field elements or 96 bytes; 4 elements are reserved for rate
 /// and 2 elements are reserved for capacity.
 pub const STATE_WIDTH: usize = 6;
@@ -27,13 +34,28 @@ pub const NUM_ROUNDS: usize = 7;
 /// Minimum cycle length required to describe Rescue permutation.
 pub const CYCLE_LENGTH: usize = 8;
 
+/// Index of the capacity element holding the domain tag of variable-length messages.
+pub const DOMAIN_IDX: usize = RATE_WIDTH;
+
+/// Index of the capacity element holding the length of fixed-length messages; it is zero for
+/// variable-length messages, which are padded instead.
+pub const LENGTH_IDX: usize = RATE_WIDTH + 1;
+
+/// Domain tag of byte strings hashed with [Hasher::hash]; application domains passed to
+/// [Rescue128::with_domain] must use other tags.
+pub const BYTES_DOMAIN: BaseElement = BaseElement::new(1);
+
+/// Number of bytes packed into one field element; 15 bytes stay below the 128-bit modulus.
+pub const BYTES_PER_ELEMENT: usize = 15;
+
 // TYPES AND INTERFACES
 // ================================================================================================
 
-pub struct Rescue128 {
-    state: [BaseElement; STATE_WIDTH],
-    idx: usize,
-}
+/// The instance of the generic [Rescue] sponge over the STARK field, see
+/// [Rescue128::parameters]. Its permutation is also hardcoded below, as the rounds of the
+/// Merkle AIR and [Rescue128::digest_fixed_length] are computed with the constant tables.
+#[derive(Clone)]
+pub struct Rescue128(Rescue<BaseElement>);
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
 pub struct Hash([BaseElement; DIGEST_SIZE]);
@@ -42,58 +64,101 @@ pub struct Hash([BaseElement; DIGEST_SIZE]);
 // ================================================================================================
 
 impl Rescue128 {
-    /// Returns a new hasher with the state initialized to all zeros.
+    /// Returns a new hasher for the default domain.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Rescue128 {
-            state: [BaseElement::ZERO; STATE_WIDTH],
-            idx: 0,
-        }
+        Self::with_domain(BaseElement::ZERO)
+    }
+
+    /// Returns a new hasher with `domain` written into the capacity, so that messages hashed
+    /// for different purposes (e.g. program commitments and state roots) never collide.
+    pub fn with_domain(domain: BaseElement) -> Self {
+        Rescue128(Rescue::with_domain(&Self::parameters(), domain))
     }
 
     /// Absorbs data into the hasher state.
     pub fn update(&mut self, data: &[BaseElement]) {
-        for &element in data {
-            self.state[self.idx] += element;
-            self.idx += 1;
-            if self.idx % RATE_WIDTH == 0 {
-                apply_permutation(&mut self.state);
-                self.idx = 0;
-            }
-        }
+        self.0.update(data)
     }
 
     /// Returns hash of the data absorbed into the hasher.
-    pub fn finalize(mut self) -> Hash {
-        if self.idx > 0 {
-            // TODO: apply proper padding
-            apply_permutation(&mut self.state);
-        }
-        Hash([self.state[0], self.state[1]])
+    ///
+    /// The message is always padded with a single ONE followed by zeros up to the end of the
+    /// rate (10* padding). This makes padding injective: a message filling the rate exactly
+    /// gets an extra padding block, and trailing zeros can not be confused with padding.
+    pub fn finalize(self) -> Hash {
+        let digest = self.0.finalize();
+        Hash([digest[0], digest[1]])
+    }
+
+    /// Returns the next `num_elements` elements of the rate; the first squeeze after absorbing
+    /// applies the same padding as [Rescue128::finalize].
+    pub fn squeeze(&mut self, num_elements: usize) -> Vec<BaseElement> {
+        self.0.squeeze(num_elements)
     }
 
     /// Returns hash of the provided data.
     pub fn digest(data: &[BaseElement]) -> Hash {
-        // initialize state to all zeros
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Returns hash of data whose length is fixed by the context, such as two digests being
+    /// merged. The length is written into the capacity instead of padding the message, which
+    /// saves the padding permutation when the data fills the rate exactly.
+    pub fn digest_fixed_length(data: &[BaseElement]) -> Hash {
         let mut state = [BaseElement::ZERO; STATE_WIDTH];
+        state[LENGTH_IDX] = BaseElement::from(data.len() as u64);
 
-        let mut i = 0;
-        for &element in data.iter() {
-            state[i] += element;
-            i += 1;
-            if i % RATE_WIDTH == 0 {
-                apply_permutation(&mut state);
-                i = 0;
-            }
+        if data.is_empty() {
+            apply_permutation(&mut state);
         }
-
-        if i > 0 {
-            // TODO: apply proper padding
+        for chunk in data.chunks(RATE_WIDTH) {
+            for (i, &element) in chunk.iter().enumerate() {
+                state[i] += element;
+            }
             apply_permutation(&mut state);
         }
 
         Hash([state[0], state[1]])
     }
+
+    /// Returns this instance as [RescueParameters], which the sponge runs on.
+    ///
+    /// The instance predates the parameter generator: it uses `ALPHA = 5` and 7 rounds to fit
+    /// the AIR cycle, where `RescueParameters::new(6, 2, 128)` would derive `alpha = 3` and 11
+    /// rounds. The hardcoded tables are kept because proofs and roots depend on them.
+    pub fn parameters() -> RescueParameters<BaseElement> {
+        RescueParameters {
+            width: STATE_WIDTH,
+            rate: RATE_WIDTH,
+            rounds: NUM_ROUNDS,
+            alpha: ALPHA as u64,
+            inv_alpha: vec![INV_ALPHA as u64, (INV_ALPHA >> 64) as u64],
+            mds: MDS.chunks(STATE_WIDTH).map(|row| row.to_vec()).collect(),
+            ark: ARK[..NUM_ROUNDS]
+                .iter()
+                .map(|round| round.to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl AlgebraicSponge<BaseElement> for Rescue128 {
+    type Parameters = ();
+
+    fn with_domain(_params: &(), domain: BaseElement) -> Self {
+        Rescue128::with_domain(domain)
+    }
+
+    fn absorb(&mut self, elements: &[BaseElement]) {
+        self.update(elements)
+    }
+
+    fn squeeze(&mut self, num_elements: usize) -> Vec<BaseElement> {
+        Rescue128::squeeze(self, num_elements)
+    }
 }
 
 // HASHER IMPLEMENTATION
@@ -102,19 +167,43 @@ impl Rescue128 {
 impl Hasher for Rescue128 {
     type Digest = Hash;
 
-    fn hash(_bytes: &[u8]) -> Self::Digest {
-        unimplemented!("not implemented")
+    /// Digest is two 128-bit field elements, which gives 128-bit collision resistance.
+    const COLLISION_RESISTANCE: u32 = 128;
+
+    fn hash(bytes: &[u8]) -> Self::Digest {
+        let mut hasher = Self::with_domain(BYTES_DOMAIN);
+        hasher.update(&bytes_to_elements(bytes));
+        hasher.finalize()
     }
 
     fn merge(values: &[Self::Digest; 2]) -> Self::Digest {
-        Self::digest(Hash::hashes_as_elements(values))
+        // both digests fill the rate exactly; copying them into a stack buffer avoids both an
+        // allocation and any assumption about the memory layout of `Hash`
+        let [a, b] = values;
+        Self::digest_fixed_length(&[a.0[0], a.0[1], b.0[0], b.0[1]])
+    }
+
+    fn merge_with_int(seed: Self::Digest, value: u64) -> Self::Digest {
+        // hash(seed || value), the three elements make it a different length than `merge`
+        Self::digest_fixed_length(&[seed.0[0], seed.0[1], BaseElement::from(value)])
     }
+}
+
+impl ElementHasher for Rescue128 {
+    type BaseField = BaseElement;
 
-    fn merge_with_int(_seed: Self::Digest, _value: u64) -> Self::Digest {
-        unimplemented!("not implemented")
+    fn hash_elements<E: FieldElement<BaseField = Self::BaseField>>(elements: &[E]) -> Self::Digest {
+        Self::digest(E::as_base_elements(elements))
     }
 }
 
+/// Canonical encoding of bytes into field elements: a 0x01 terminator is appended, then the
+/// bytes are packed little-endian into elements of [BYTES_PER_ELEMENT] bytes each. The
+/// terminator keeps trailing zero bytes significant, so the encoding is injective.
+pub fn bytes_to_elements(bytes: &[u8]) -> Vec<BaseElement> {
+    rescue::bytes_to_elements(bytes)
+}
+
 // HASH IMPLEMENTATION
 // ================================================================================================
 
@@ -137,10 +226,9 @@ impl Hash {
         self.0
     }
 
-    pub fn hashes_as_elements(hashes: &[Hash]) -> &[BaseElement] {
-        let p = hashes.as_ptr();
-        let len = hashes.len() * DIGEST_SIZE;
-        unsafe { slice::from_raw_parts(p as *const BaseElement, len) }
+    /// Returns the elements of all `hashes` in order, without copying them.
+    pub fn hashes_as_elements(hashes: &[Hash]) -> impl Iterator<Item = &BaseElement> {
+        hashes.iter().flat_map(|hash| hash.0.iter())
     }
 }
 
@@ -515,3 +603,227 @@ pub const ARK: [[BaseElement; STATE_WIDTH * 2]; CYCLE_LENGTH] = [
         BaseElement::new(290826558340641225374953827677533570165),
     ],
 ];
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elements(values: &[u128]) -> Vec<BaseElement> {
+        values.iter().map(|&v| BaseElement::new(v)).collect()
+    }
+
+    fn hash(v1: u128, v2: u128) -> Hash {
+        Hash::new(BaseElement::new(v1), BaseElement::new(v2))
+    }
+
+    #[test]
+    fn digest_test_vectors() {
+        let vectors = [
+            (
+                elements(&[]),
+                hash(
+                    148620668608008153957302962763161980467,
+                    316714421380042976830867249467147920627,
+                ),
+            ),
+            (
+                elements(&[1]),
+                hash(
+                    249796525734495166953435250490238130161,
+                    322650264461919153095767643161514610068,
+                ),
+            ),
+            (
+                elements(&[1, 0]),
+                hash(
+                    185991561626412838495508003942529018610,
+                    215502363665528327786579638959655314843,
+                ),
+            ),
+            (
+                elements(&[1, 2, 3, 4]),
+                hash(
+                    22286020324564657979771586453443526916,
+                    31433200141305522900165919014395270616,
+                ),
+            ),
+            (
+                elements(&[1, 2, 3, 4, 5, 6]),
+                hash(
+                    129338096604290750387922463317672557897,
+                    333563181587052943247421928743794630292,
+                ),
+            ),
+        ];
+        for (data, expected) in vectors.iter() {
+            assert_eq!(Rescue128::digest(data), *expected);
+        }
+    }
+
+    #[test]
+    fn padding_is_injective() {
+        let a = BaseElement::new(42);
+        let zero = BaseElement::ZERO;
+        let one = BaseElement::ONE;
+        // trailing zeros and explicit padding elements must not collide with shorter messages
+        let messages = [
+            vec![],
+            vec![zero],
+            vec![a],
+            vec![a, zero],
+            vec![a, one],
+            vec![a, zero, zero, zero],
+            vec![a, one, zero, zero],
+            vec![a, zero, zero, zero, zero],
+        ];
+        let digests: Vec<Hash> = messages.iter().map(|m| Rescue128::digest(m)).collect();
+        for i in 0..digests.len() {
+            for j in i + 1..digests.len() {
+                assert_ne!(
+                    digests[i], digests[j],
+                    "{:?} and {:?}",
+                    messages[i], messages[j]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn update_matches_digest() {
+        let data = elements(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        for split in 0..data.len() {
+            let mut hasher = Rescue128::new();
+            hasher.update(&data[..split]);
+            hasher.update(&data[split..]);
+            assert_eq!(hasher.finalize(), Rescue128::digest(&data));
+        }
+    }
+
+    #[test]
+    fn domains_are_separated() {
+        let data = elements(&[1]);
+        let mut hasher = Rescue128::with_domain(BaseElement::new(7));
+        hasher.update(&data);
+        let digest = hasher.finalize();
+        assert_eq!(
+            digest,
+            hash(
+                274093691852417228113477178765810070479,
+                306490228953492613260801121347087488455,
+            )
+        );
+        assert_ne!(digest, Rescue128::digest(&data));
+        assert_ne!(
+            Rescue128::digest_fixed_length(&data),
+            Rescue128::digest(&data)
+        );
+    }
+
+    #[test]
+    fn hash_bytes() {
+        let digest = Rescue128::hash(b"verifiable runtime");
+        assert_eq!(digest, Rescue128::hash(b"verifiable runtime"));
+        assert_ne!(Rescue128::hash(&[1]), Rescue128::hash(&[1, 0]));
+        assert_ne!(Rescue128::hash(&[]), Rescue128::hash(&[0]));
+        assert_ne!(Rescue128::hash(&[0; 15]), Rescue128::hash(&[0; 16]));
+        assert_ne!(
+            Rescue128::hash(&[7]),
+            Rescue128::digest(&bytes_to_elements(&[7]))
+        );
+    }
+
+    #[test]
+    fn merge_with_int_differs_by_value() {
+        let seed = Rescue128::digest(&elements(&[1]));
+        assert_ne!(
+            Rescue128::merge_with_int(seed, 0),
+            Rescue128::merge_with_int(seed, 1)
+        );
+        assert_ne!(Rescue128::merge_with_int(seed, 0), seed);
+    }
+
+    #[test]
+    fn random_coin_and_merkle_tree() {
+        use winterfell::crypto::{MerkleTree, RandomCoin};
+
+        let mut coin = RandomCoin::<BaseElement, Rescue128>::new(&[1, 2, 3]);
+        let first: BaseElement = coin.draw().unwrap();
+        let second: BaseElement = coin.draw().unwrap();
+        assert_ne!(first, second);
+
+        let leaves = (0..8u128)
+            .map(|i| Rescue128::digest(&elements(&[i])))
+            .collect::<Vec<_>>();
+        let tree = MerkleTree::<Rescue128>::new(leaves.clone()).unwrap();
+        let proof = tree.prove(5).unwrap();
+        assert!(MerkleTree::<Rescue128>::verify(*tree.root(), 5, &proof).is_ok());
+    }
+
+    #[test]
+    fn proves_dvm_program() {
+        use crate::stark::{build_trace, default_proof_options, DVMAir, DVMProver, PublicInputs};
+        use winterfell::Prover;
+
+        // PUSH 7, PUSH 2, DIV, RET
+        let program = vec![0x05u8, 0, 0, 0, 7, 0x05, 0, 0, 0, 2, 0x04, 0x07];
+        let trace = build_trace(&program);
+        let prover = DVMProver::<Rescue128>::with_hasher(default_proof_options(), program.clone());
+        let proof = prover.prove(trace).unwrap();
+        let pub_inputs = PublicInputs {
+            program,
+            result: BaseElement::new(3),
+        };
+        assert!(winterfell::verify::<DVMAir, Rescue128>(proof, pub_inputs).is_ok());
+    }
+
+    #[test]
+    fn merge_test_vector() {
+        let a = Rescue128::digest(&elements(&[1]));
+        let b = Rescue128::digest(&elements(&[2]));
+        assert_eq!(
+            Rescue128::merge(&[a, b]),
+            hash(
+                234975043415791721944479157614274690417,
+                183577051206192022420309081330907568712,
+            )
+        );
+        assert_ne!(Rescue128::merge(&[a, b]), Rescue128::merge(&[b, a]));
+    }
+
+    #[test]
+    fn parameters_match_hardcoded_permutation() {
+        // the sponge runs on the parameters, the AIRs and fixed-length digests on the tables
+        let params = Rescue128::parameters();
+        let mut state = [BaseElement::ZERO; STATE_WIDTH];
+        for (i, x) in state.iter_mut().enumerate() {
+            *x = BaseElement::new(i as u128 + 1);
+        }
+        let mut expected = state;
+        apply_permutation(&mut expected);
+        rescue::apply_permutation(&params, &mut state);
+        assert_eq!(state, expected);
+    }
+
+    #[test]
+    fn derives_stark_field_parameters() {
+        use crate::hash::rescue::{Rescue, RescueField};
+
+        let params = RescueParameters::<BaseElement>::new(STATE_WIDTH, 2, 128);
+        assert_eq!(params.alpha, 3);
+        assert_eq!(params.rounds, 11);
+        assert_eq!(params.mds.len(), STATE_WIDTH);
+
+        let x = BaseElement::new(123456789);
+        assert_eq!(x.power(&[params.alpha]).power(&params.inv_alpha), x);
+
+        let digest = Rescue::digest(&params, &elements(&[1, 2, 3]));
+        assert_eq!(digest.len(), 2);
+        assert_ne!(
+            digest,
+            Rescue128::digest(&elements(&[1, 2, 3])).to_elements()
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! R1CS gadget computing the same sponge as [Rescue128] inside an arkworks circuit over a larger
+//! prime field, such as the BLS12-377 scalar field.
+//!
+//! Elements of the 128-bit STARK field are emulated: each one is a canonical integer below the
+//! STARK modulus, held in a native variable together with its two 64-bit limbs. Products are
+//! computed limb by limb, so every intermediate value stays far below the native modulus and
+//! is reduced with a witnessed quotient and remainder that hold over the integers.
+use super::{Rescue128, DOMAIN_IDX, RATE_WIDTH, STATE_WIDTH};
+use crate::hash::{rescue::RescueParameters, AlgebraicSpongeGadget};
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::fp::FpVar, prelude::FieldVar, R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use num_bigint::BigUint;
+use winterfell::math::{fields::f128::BaseElement, FieldElement, StarkField};
+
+/// Modulus of the STARK field
+const MODULUS: u128 = BaseElement::MODULUS;
+
+/// `2^128 mod p`, which folds the product of the high limbs back below `2^128`
+const FOLD: u128 = 0u128.wrapping_sub(MODULUS);
+
+/// Products of two elements stay below `2^194`, see [product]
+const PRODUCT_BITS: usize = 194;
+
+/// Rows of the MDS matrix sum six products and a round constant
+const ROW_BITS: usize = PRODUCT_BITS + 3;
+
+// EMULATED ELEMENTS
+// ================================================================================================
+
+/// Element of the STARK field: a canonical integer below [MODULUS] and its 64-bit limbs
+#[derive(Clone)]
+struct Element<F: PrimeField> {
+    value: FpVar<F>,
+    limbs: [FpVar<F>; 2],
+}
+
+impl<F: PrimeField> Element<F> {
+    fn constant(value: u128) -> Self {
+        let constant = |value: u128| FpVar::constant(F::from(value));
+        Element {
+            value: constant(value),
+            limbs: [constant(value & u64::MAX as u128), constant(value >> 64)],
+        }
+    }
+
+    /// Allocates the canonical element `value`, range-checking both limbs and the gap to the
+    /// modulus with bit decompositions.
+    fn new_witness(
+        cs: ConstraintSystemRef<F>,
+        value: Result<u128, SynthesisError>,
+    ) -> Result<Self, SynthesisError> {
+        let low = alloc_uint(cs.clone(), value.map(|v| v & u64::MAX as u128), 64)?;
+        let high = alloc_uint(cs.clone(), value.map(|v| v >> 64), 64)?;
+        let gap = alloc_uint(cs, value.map(|v| (MODULUS - 1).wrapping_sub(v)), 128)?;
+
+        let value = &low + &high * F::from(1u128 << 64);
+        (&value + &gap).enforce_equal(&FpVar::constant(F::from(MODULUS - 1)))?;
+        Ok(Element {
+            value,
+            limbs: [low, high],
+        })
+    }
+
+    /// Native value of the element, missing while generating keys
+    fn native(&self) -> Result<u128, SynthesisError> {
+        to_u128(&self.value)
+    }
+}
+
+/// Allocates `bits` boolean witnesses holding `value` and returns their sum.
+fn alloc_uint<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    value: Result<u128, SynthesisError>,
+    bits: usize,
+) -> Result<FpVar<F>, SynthesisError> {
+    alloc_big_uint(cs, value.map(BigUint::from), bits)
+}
+
+fn alloc_big_uint<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    value: Result<BigUint, SynthesisError>,
+    bits: usize,
+) -> Result<FpVar<F>, SynthesisError> {
+    let bits = (0..bits as u64)
+        .map(|i| {
+            let bit = value.as_ref().map(|v| v.bit(i)).map_err(|e| *e);
+            Boolean::new_witness(cs.clone(), || bit)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Boolean::le_bits_to_fp_var(&bits)
+}
+
+/// Reduces `t`, known to be below `2^bits`, modulo the STARK modulus.
+fn reduce<F: PrimeField>(t: &FpVar<F>, bits: usize) -> Result<Element<F>, SynthesisError> {
+    let modulus = BigUint::from(MODULUS);
+    if let FpVar::Constant(c) = t {
+        return Ok(Element::constant(from_digits(&(to_big_uint(*c) % modulus))));
+    }
+
+    let cs = t.cs();
+    let t_value = t
+        .value()
+        .map(to_big_uint)
+        .map_err(|_| SynthesisError::AssignmentMissing);
+    let remainder = t_value.as_ref().map(|t| from_digits(&(t % &modulus)));
+
+    let element = Element::new_witness(cs.clone(), remainder.map_err(|e| *e))?;
+    // the quotient is below 2^(bits - 127), so both sides stay below the native modulus
+    let quotient = alloc_big_uint(cs, t_value.map(|t| t / modulus), bits - 127)?;
+    (quotient * F::from(MODULUS) + &element.value).enforce_equal(t)?;
+    Ok(element)
+}
+
+/// Unreduced product of two elements. With `a = a1 * 2^64 + a0` and `2^128 = FOLD mod p`, it
+/// is `a1 * b1 * FOLD + (a1 * b0 + a0 * b1) * 2^64 + a0 * b0`, which is below `2^194`.
+fn product<F: PrimeField>(a: &Element<F>, b: &Element<F>) -> FpVar<F> {
+    let [a0, a1] = &a.limbs;
+    let [b0, b1] = &b.limbs;
+    a1 * b1 * F::from(FOLD) + (a1 * b0 + a0 * b1) * F::from(1u128 << 64) + a0 * b0
+}
+
+fn mul<F: PrimeField>(a: &Element<F>, b: &Element<F>) -> Result<Element<F>, SynthesisError> {
+    reduce(&product(a, b), PRODUCT_BITS)
+}
+
+fn add<F: PrimeField>(a: &Element<F>, b: &Element<F>) -> Result<Element<F>, SynthesisError> {
+    reduce(&(&a.value + &b.value), 129)
+}
+
+/// Raises `x` to the power of `exp` by square and multiply.
+fn power<F: PrimeField>(x: &Element<F>, exp: u64) -> Result<Element<F>, SynthesisError> {
+    let mut result = x.clone();
+    for i in (0..63 - exp.leading_zeros()).rev() {
+        result = mul(&result, &result)?;
+        if (exp >> i) & 1 == 1 {
+            result = mul(&result, x)?;
+        }
+    }
+    Ok(result)
+}
+
+fn to_u128<F: PrimeField>(value: &FpVar<F>) -> Result<u128, SynthesisError> {
+    Ok(from_digits(&to_big_uint(value.value()?)))
+}
+
+fn to_big_uint<F: PrimeField>(value: F) -> BigUint {
+    value.into_repr().into()
+}
+
+/// Returns the lowest 128 bits of `value`
+fn from_digits(value: &BigUint) -> u128 {
+    let digits = value.to_u64_digits();
+    digits
+        .iter()
+        .take(2)
+        .rev()
+        .fold(0u128, |acc, &d| (acc << 64) | d as u128)
+}
+
+// GADGET
+// ================================================================================================
+
+/// In-circuit counterpart of [Rescue128]; elements are passed in and out as native variables
+/// holding canonical STARK field elements.
+#[derive(Clone)]
+pub struct Rescue128Gadget<F: PrimeField> {
+    params: RescueParameters<BaseElement>,
+    cs: ConstraintSystemRef<F>,
+    state: Vec<Element<F>>,
+    idx: usize,
+    squeezing: bool,
+}
+
+impl<F: PrimeField> Rescue128Gadget<F> {
+    /// Returns a new hasher with `domain` written into the capacity, like
+    /// [Rescue128::with_domain].
+    pub fn with_domain(cs: ConstraintSystemRef<F>, domain: BaseElement) -> Self {
+        assert!(
+            F::size_in_bits() > ROW_BITS,
+            "the native field is too small to emulate the STARK field"
+        );
+        let mut state = vec![Element::constant(0); STATE_WIDTH];
+        state[DOMAIN_IDX] = Element::constant(domain.as_int());
+        Rescue128Gadget {
+            params: Rescue128::parameters(),
+            cs,
+            state,
+            idx: 0,
+            squeezing: false,
+        }
+    }
+
+    /// Absorbs data into the hasher state; each element must be below the STARK modulus.
+    pub fn update(&mut self, data: &[FpVar<F>]) -> Result<(), SynthesisError> {
+        if self.squeezing {
+            self.squeezing = false;
+            self.idx = 0;
+        }
+        for x in data {
+            let element = match x {
+                FpVar::Constant(c) => reduce(&FpVar::Constant(*c), F::size_in_bits())?,
+                FpVar::Var(_) => {
+                    let element = Element::new_witness(self.cs.clone(), to_u128(x))?;
+                    element.value.enforce_equal(x)?;
+                    element
+                }
+            };
+            self.state[self.idx] = add(&self.state[self.idx], &element)?;
+            self.idx += 1;
+            if self.idx == RATE_WIDTH {
+                self.apply_permutation()?;
+                self.idx = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next `num_elements` elements of the rate, padding the input first like
+    /// [Rescue128::squeeze].
+    pub fn squeeze(&mut self, num_elements: usize) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        if !self.squeezing {
+            self.state[self.idx] = add(&self.state[self.idx], &Element::constant(1))?;
+            self.apply_permutation()?;
+            self.squeezing = true;
+            self.idx = 0;
+        }
+        let mut result = Vec::with_capacity(num_elements);
+        for _ in 0..num_elements {
+            if self.idx == RATE_WIDTH {
+                self.apply_permutation()?;
+                self.idx = 0;
+            }
+            result.push(self.state[self.idx].value.clone());
+            self.idx += 1;
+        }
+        Ok(result)
+    }
+
+    fn apply_permutation(&mut self) -> Result<(), SynthesisError> {
+        let params = self.params.clone();
+        for ark in params.ark.iter() {
+            // apply first half of Rescue round
+            for x in self.state.iter_mut() {
+                *x = power(x, params.alpha)?;
+            }
+            self.apply_mds(&ark[..STATE_WIDTH])?;
+
+            // apply second half of Rescue round; the inverse S-box is witnessed and checked
+            // with the forward S-box
+            for x in self.state.iter_mut() {
+                let inverse = x.native().map(|x| {
+                    let x = BaseElement::new(x);
+                    x.exp(inv_alpha(&params)).as_int()
+                });
+                let y = Element::new_witness(self.cs.clone(), inverse)?;
+                power(&y, params.alpha)?.value.enforce_equal(&x.value)?;
+                *x = y;
+            }
+            self.apply_mds(&ark[STATE_WIDTH..])?;
+        }
+        Ok(())
+    }
+
+    /// Multiplies the state by the MDS matrix and adds the round constants `ark`.
+    fn apply_mds(&mut self, ark: &[BaseElement]) -> Result<(), SynthesisError> {
+        let mut state = Vec::with_capacity(STATE_WIDTH);
+        for (row, &c) in self.params.mds.iter().zip(ark) {
+            let sum = row
+                .iter()
+                .zip(self.state.iter())
+                .map(|(&m, x)| product(&Element::constant(m.as_int()), x))
+                .fold(FpVar::constant(F::from(c.as_int())), |acc, term| acc + term);
+            state.push(reduce(&sum, ROW_BITS)?);
+        }
+        self.state = state;
+        Ok(())
+    }
+}
+
+fn inv_alpha(params: &RescueParameters<BaseElement>) -> u128 {
+    params
+        .inv_alpha
+        .iter()
+        .rev()
+        .fold(0u128, |acc, &limb| (acc << 64) | limb as u128)
+}
+
+impl<F: PrimeField> AlgebraicSpongeGadget<F> for Rescue128Gadget<F> {
+    type Parameters = ();
+
+    /// `domain` must be below the STARK modulus
+    fn with_domain(cs: ConstraintSystemRef<F>, _params: &(), domain: F) -> Self {
+        Rescue128Gadget::with_domain(cs, BaseElement::new(from_digits(&to_big_uint(domain))))
+    }
+
+    fn absorb(&mut self, elements: &[FpVar<F>]) -> Result<(), SynthesisError> {
+        self.update(elements)
+    }
+
+    fn squeeze(&mut self, num_elements: usize) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        Rescue128Gadget::squeeze(self, num_elements)
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Fr;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn to_fr(value: BaseElement) -> Fr {
+        Fr::from(value.as_int())
+    }
+
+    #[test]
+    fn squeeze_matches_native() {
+        let domain = BaseElement::new(7);
+        for len in [0u128, 5] {
+            let data: Vec<BaseElement> = (1..=len).map(|v| BaseElement::new(v << 100)).collect();
+            let mut native = Rescue128::with_domain(domain);
+            native.update(&data);
+            let expected: Vec<Fr> = native.squeeze(2).into_iter().map(to_fr).collect();
+
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let values: Vec<Fr> = data.iter().map(|&v| to_fr(v)).collect();
+            let vars = Vec::new_witness(cs.clone(), || Ok(values)).unwrap();
+            let mut hasher = Rescue128Gadget::with_domain(cs.clone(), domain);
+            hasher.update(&vars).unwrap();
+
+            assert_eq!(hasher.squeeze(2).unwrap().value().unwrap(), expected);
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn non_canonical_input_is_unsatisfied() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let x = FpVar::new_witness(cs.clone(), || Ok(Fr::from(MODULUS) + Fr::from(1u64))).unwrap();
+        let mut hasher = Rescue128Gadget::with_domain(cs.clone(), BaseElement::ZERO);
+        hasher.update(&[x]).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
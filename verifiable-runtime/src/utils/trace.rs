@@ -0,0 +1,269 @@
+//! Export, comparison and constraint inspection of execution traces, to debug AIRs without
+//! reading [print_trace](super::print_trace) output by hand.
+//!
+//! Values are written as their canonical integers, in decimal. JSON values are strings, since
+//! 128-bit integers do not fit into a JSON number.
+use core::ops::Range;
+use std::io::{self, Write};
+use winterfell::{
+    math::{FieldElement, StarkField},
+    Air, EvaluationFrame, Trace, TraceTable,
+};
+
+// EXPORT
+// ================================================================================================
+
+/// Writes `rows` of the trace as CSV, one row per step with a leading `step` column.
+pub fn write_csv<E: StarkField, W: Write>(
+    trace: &TraceTable<E>,
+    rows: Range<usize>,
+    mut writer: W,
+) -> io::Result<()> {
+    let header: Vec<String> = (0..trace.width()).map(|i| format!("col_{}", i)).collect();
+    writeln!(writer, "step,{}", header.join(","))?;
+    for step in clamp(rows, trace.length()) {
+        writeln!(writer, "{},{}", step, row_values(trace, step).join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes `rows` of the trace as a JSON object with the trace `width` and `length` and the
+/// exported `rows`, each holding its `step` and its `values`.
+pub fn write_json<E: StarkField, W: Write>(
+    trace: &TraceTable<E>,
+    rows: Range<usize>,
+    mut writer: W,
+) -> io::Result<()> {
+    write!(
+        writer,
+        "{{\"width\":{},\"length\":{},\"rows\":[",
+        trace.width(),
+        trace.length()
+    )?;
+    for (i, step) in clamp(rows, trace.length()).enumerate() {
+        let values: Vec<String> = row_values(trace, step)
+            .iter()
+            .map(|value| format!("\"{}\"", value))
+            .collect();
+        let separator = if i == 0 { "" } else { "," };
+        write!(
+            writer,
+            "{}\n{{\"step\":{},\"values\":[{}]}}",
+            separator,
+            step,
+            values.join(",")
+        )?;
+    }
+    writeln!(writer, "\n]}}")
+}
+
+// DIFF
+// ================================================================================================
+
+/// A cell holding different values in two traces; the value is `None` on the side whose trace
+/// has no such cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellDiff<E> {
+    pub step: usize,
+    pub column: usize,
+    pub left: Option<E>,
+    pub right: Option<E>,
+}
+
+/// Returns every cell where `left` and `right` differ, ordered by step and then by column.
+/// Traces of different shapes are compared over the union of their cells.
+pub fn diff_traces<E: StarkField>(left: &TraceTable<E>, right: &TraceTable<E>) -> Vec<CellDiff<E>> {
+    let cell = |trace: &TraceTable<E>, column, step| {
+        (column < trace.width() && step < trace.length()).then(|| trace.get(column, step))
+    };
+
+    let mut diffs = Vec::new();
+    for step in 0..left.length().max(right.length()) {
+        for column in 0..left.width().max(right.width()) {
+            let (a, b) = (cell(left, column, step), cell(right, column, step));
+            if a != b {
+                diffs.push(CellDiff {
+                    step,
+                    column,
+                    left: a,
+                    right: b,
+                });
+            }
+        }
+    }
+    diffs
+}
+
+/// Prints the differences between two traces, one cell per line.
+pub fn print_diff<E: StarkField>(left: &TraceTable<E>, right: &TraceTable<E>) {
+    let show = |value: Option<E>| value.map_or("-".to_string(), |v| format!("{:?}", v.as_int()));
+    for diff in diff_traces(left, right) {
+        println!(
+            "{}\tcol {}\t{} != {}",
+            diff.step,
+            diff.column,
+            show(diff.left),
+            show(diff.right)
+        );
+    }
+}
+
+// CONSTRAINT INSPECTION
+// ================================================================================================
+
+/// Returns the steps at which transition constraint `constraint` of `air` does not evaluate to
+/// zero; the step of a transition is the index of its current row.
+pub fn failing_rows<A: Air>(
+    air: &A,
+    trace: &TraceTable<A::BaseField>,
+    constraint: usize,
+) -> Vec<usize> {
+    let num_constraints = air.context().num_main_transition_constraints();
+    assert!(
+        constraint < num_constraints,
+        "constraint {} does not exist, the AIR has {}",
+        constraint,
+        num_constraints
+    );
+
+    let periodic_columns = air.get_periodic_column_values();
+    let num_transitions = trace.length() - air.context().num_transition_exemptions();
+    let mut current = vec![A::BaseField::ZERO; trace.width()];
+    let mut next = vec![A::BaseField::ZERO; trace.width()];
+    let mut result = vec![A::BaseField::ZERO; num_constraints];
+
+    (0..num_transitions)
+        .filter(|&step| {
+            trace.read_row_into(step, &mut current);
+            trace.read_row_into(step + 1, &mut next);
+            let frame = EvaluationFrame::from_rows(current.clone(), next.clone());
+            let periodic_values: Vec<A::BaseField> = periodic_columns
+                .iter()
+                .map(|column| column[step % column.len()])
+                .collect();
+
+            result.fill(A::BaseField::ZERO);
+            air.evaluate_transition(&frame, &periodic_values, &mut result);
+            result[constraint] != A::BaseField::ZERO
+        })
+        .collect()
+}
+
+/// Prints `columns` of every row like [print_trace](super::print_trace), marking with `>>` the
+/// rows at which transition constraint `constraint` of `air` does not hold.
+pub fn print_failing_rows<A: Air>(
+    air: &A,
+    trace: &TraceTable<A::BaseField>,
+    constraint: usize,
+    columns: Range<usize>,
+) {
+    let failing = failing_rows(air, trace, constraint);
+    for step in 0..trace.length() {
+        let marker = if failing.contains(&step) { ">>" } else { "" };
+        println!(
+            "{}{}\t[{}]",
+            marker,
+            step,
+            row_values(trace, step)[columns.clone()].join(", ")
+        );
+    }
+}
+
+// HELPER FUNCTIONS
+// ================================================================================================
+
+fn clamp(rows: Range<usize>, length: usize) -> Range<usize> {
+    rows.start.min(length)..rows.end.min(length)
+}
+
+fn row_values<E: StarkField>(trace: &TraceTable<E>, step: usize) -> Vec<String> {
+    (0..trace.width())
+        .map(|column| format!("{:?}", trace.get(column, step).as_int()))
+        .collect()
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stark::{build_trace, default_proof_options, to_element, DVMAir, PublicInputs};
+    use winterfell::math::fields::f128::BaseElement;
+
+    /// PUSH 6, PUSH 7, MUL, RET
+    const PROGRAM: [u8; 12] = [
+        0x05, 0x00, 0x00, 0x00, 0x06, 0x05, 0x00, 0x00, 0x00, 0x07, 0x03, 0x07,
+    ];
+
+    fn air(trace: &TraceTable<BaseElement>) -> DVMAir {
+        let pub_inputs = PublicInputs {
+            program: PROGRAM.to_vec(),
+            result: to_element(42),
+        };
+        DVMAir::new(trace.get_info(), pub_inputs, default_proof_options())
+    }
+
+    #[test]
+    fn exports_csv_and_json() {
+        let trace = build_trace(&PROGRAM);
+        let mut csv = Vec::new();
+        write_csv(&trace, 1..3, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("step,col_0,col_1,"));
+        // the program counter of step 1 points past the first PUSH
+        assert!(lines[1].starts_with("1,5,"));
+
+        let mut json = Vec::new();
+        write_json(&trace, 0..100, &mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(&format!(
+            "{{\"width\":{},\"length\":{},",
+            trace.width(),
+            trace.length()
+        )));
+        assert_eq!(json.matches("\"step\"").count(), trace.length());
+    }
+
+    #[test]
+    fn diffs_traces() {
+        let trace = build_trace(&PROGRAM);
+        assert!(diff_traces(&trace, &trace).is_empty());
+
+        let mut other = build_trace(&PROGRAM);
+        other.set(0, 2, BaseElement::new(99));
+        assert_eq!(
+            diff_traces(&trace, &other),
+            vec![CellDiff {
+                step: 2,
+                column: 0,
+                left: Some(trace.get(0, 2)),
+                right: Some(BaseElement::new(99)),
+            }]
+        );
+
+        let wider = TraceTable::init(vec![vec![BaseElement::ZERO; 8]; 2]);
+        let narrower = TraceTable::init(vec![vec![BaseElement::ZERO; 8]; 1]);
+        let diffs = diff_traces(&wider, &narrower);
+        assert_eq!(diffs.len(), 8);
+        assert!(diffs
+            .iter()
+            .all(|diff| diff.column == 1 && diff.right.is_none()));
+    }
+
+    #[test]
+    fn finds_failing_rows() {
+        let trace = build_trace(&PROGRAM);
+        let air = air(&trace);
+        for constraint in 0..air.context().num_main_transition_constraints() {
+            assert!(failing_rows(&air, &trace, constraint).is_empty());
+        }
+
+        // a wrong program counter breaks the transitions into and out of step 2
+        let mut trace = trace;
+        trace.set(0, 2, BaseElement::new(99));
+        assert_eq!(failing_rows(&air, &trace, 0), vec![1, 2]);
+    }
+}